@@ -0,0 +1,26 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use std::hint::black_box;
+
+#[path = "../src/scan.rs"]
+mod scan;
+
+fn bench_scan(c: &mut Criterion) {
+    // One 20ms analysis window of 8 channels at 192kHz.
+    let samples: Vec<f32> = (0..192_000 / 50 * 8)
+        .map(|n| (n as f32 * 0.01).sin())
+        .collect();
+
+    c.bench_function("peak_and_power", |b| {
+        b.iter(|| scan::peak_and_power(black_box(&samples)))
+    });
+    c.bench_function("channel_powers", |b| {
+        let mut powers = [0f32; 8];
+        b.iter(|| scan::channel_powers(black_box(&samples), &mut powers))
+    });
+    c.bench_function("zero_crossings", |b| {
+        b.iter(|| scan::zero_crossings(black_box(&samples), 8))
+    });
+}
+
+criterion_group!(benches, bench_scan);
+criterion_main!(benches);
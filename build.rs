@@ -0,0 +1,7 @@
+//! Find libsndfile, which src/sound.rs binds to, with pkg-config.
+
+fn main() {
+    if let Err(e) = pkg_config::probe_library("sndfile") {
+        panic!("cannot find libsndfile: {e}");
+    }
+}
@@ -0,0 +1,63 @@
+//! The activity log window, which lists when the sources were on.
+
+use crate::tray::{self, TrayState};
+use gtk::prelude::*;
+
+/// Create the model for the activity log, with the source, the start and end
+/// times and the duration of each period.
+pub fn store() -> gtk::ListStore {
+    gtk::ListStore::new(&[String::static_type(); 4])
+}
+
+/// Create the activity log window, which shows the given model.
+pub fn window(store: &gtk::ListStore) -> gtk::Window {
+    let window = gtk::Window::new(gtk::WindowType::Toplevel);
+    window.set_title("pw-micclick activity");
+    window.set_default_size(480, 360);
+
+    let view = gtk::TreeView::with_model(store);
+    for (column, title) in ["Source", "On", "Off", "Duration"].into_iter().enumerate() {
+        let cell = gtk::CellRendererText::new();
+        let column_view = gtk::TreeViewColumn::new();
+        column_view.set_title(title);
+        column_view.pack_start(&cell, true);
+        column_view.add_attribute(&cell, "text", column as i32);
+        view.append_column(&column_view);
+    }
+    let scrolled = gtk::ScrolledWindow::new(None::<&gtk::Adjustment>, None::<&gtk::Adjustment>);
+    scrolled.add(&view);
+    window.add(&scrolled);
+    window
+}
+
+/// Fill the model with the activity from the tray state, newest first.
+pub fn fill(store: &gtk::ListStore, state: &TrayState) {
+    let time = |time: &glib::DateTime| {
+        time.format("%a %H:%M:%S")
+            .map(String::from)
+            .unwrap_or_default()
+    };
+    store.clear();
+    for activity in state.activity().iter().rev() {
+        let (end, duration) = match activity.duration {
+            Some(duration) => (
+                activity
+                    .start
+                    .add_seconds(duration.as_secs_f64())
+                    .map(|end| time(&end))
+                    .unwrap_or_default(),
+                tray::format_time(duration),
+            ),
+            None => (String::new(), "still on".to_owned()),
+        };
+        store.insert_with_values(
+            None,
+            &[
+                (0, &state.source_label(activity.source)),
+                (1, &time(&activity.start)),
+                (2, &end),
+                (3, &duration),
+            ],
+        );
+    }
+}
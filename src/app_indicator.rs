@@ -0,0 +1,89 @@
+//! A wrapper around libappindicator's AppIndicator object, which unlike the
+//! one in the libappindicator crate gives access to its signals.
+
+use glib::prelude::*;
+use glib::translate::{from_glib_full, ToGlibPtr};
+use libappindicator::{
+    app_indicator_new, app_indicator_set_attention_icon_full, app_indicator_set_icon_full,
+    app_indicator_set_icon_theme_path, app_indicator_set_label, app_indicator_set_menu,
+    app_indicator_set_secondary_activate_target, app_indicator_set_status, app_indicator_set_title,
+    AppIndicatorCategory, AppIndicatorStatus,
+};
+
+#[derive(Clone)]
+pub struct AppIndicator(glib::Object);
+
+impl AppIndicator {
+    pub fn new(id: &str) -> Self {
+        let category = AppIndicatorCategory::ApplicationStatus as u32;
+        // The new reference is owned by the returned object.
+        unsafe {
+            let indicator = app_indicator_new(id.to_glib_none().0, "".to_glib_none().0, category);
+            Self(from_glib_full(indicator as *mut glib::gobject_ffi::GObject))
+        }
+    }
+
+    /// Get the pointer to pass to libappindicator's functions.
+    fn raw<T>(&self) -> *mut T {
+        self.0.as_ptr() as *mut T
+    }
+
+    pub fn set_status(&self, status: AppIndicatorStatus) {
+        unsafe { app_indicator_set_status(self.raw(), status as u32) }
+    }
+
+    pub fn set_icon_full(&self, name: &str, description: &str) {
+        unsafe {
+            app_indicator_set_icon_full(
+                self.raw(),
+                name.to_glib_none().0,
+                description.to_glib_none().0,
+            )
+        }
+    }
+
+    pub fn set_attention_icon_full(&self, name: &str, description: &str) {
+        unsafe {
+            app_indicator_set_attention_icon_full(
+                self.raw(),
+                name.to_glib_none().0,
+                description.to_glib_none().0,
+            )
+        }
+    }
+
+    pub fn set_icon_theme_path(&self, path: &str) {
+        unsafe { app_indicator_set_icon_theme_path(self.raw(), path.to_glib_none().0) }
+    }
+
+    pub fn set_menu(&self, menu: &gtk::Menu) {
+        unsafe { app_indicator_set_menu(self.raw(), menu.to_glib_none().0) }
+    }
+
+    pub fn set_label(&self, label: &str, guide: &str) {
+        unsafe {
+            app_indicator_set_label(self.raw(), label.to_glib_none().0, guide.to_glib_none().0)
+        }
+    }
+
+    pub fn set_title(&self, title: &str) {
+        unsafe { app_indicator_set_title(self.raw(), title.to_glib_none().0) }
+    }
+
+    /// Activate `item` when the user middle-clicks the icon.
+    pub fn set_secondary_activate_target(&self, item: &gtk::MenuItem) {
+        let widget: &gtk::Widget = item.upcast_ref();
+        unsafe { app_indicator_set_secondary_activate_target(self.raw(), widget.to_glib_none().0) }
+    }
+
+    /// Call `f` with the direction whenever the user scrolls on the icon.
+    pub fn connect_scroll(&self, f: impl Fn(gtk::gdk::ScrollDirection) + 'static) {
+        self.0.connect_local("scroll-event", false, move |values| {
+            // The arguments are the indicator, the number of steps and the direction.
+            if let Ok(direction) = values[2].get::<gtk::gdk::ScrollDirection>() {
+                f(direction);
+            }
+            None
+        });
+    }
+}
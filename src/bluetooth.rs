@@ -0,0 +1,150 @@
+//! Watching Bluetooth headsets for switches to a profile that uses their microphone.
+
+use anyhow::Result;
+use libspa::param::ParamType;
+use libspa::pod::deserialize::PodDeserializer;
+use libspa::pod::{Pod, Value};
+use libspa_sys::SPA_PARAM_PROFILE_name;
+use pipewire::core::Core;
+use pipewire::device::{Device, DeviceListener};
+use pipewire::keys;
+use pipewire::registry::{self, Registry};
+use pipewire::types::ObjectType;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::Arc;
+
+/// Keeps the registry and device listeners alive.
+pub struct HeadsetWatcher {
+    _registry: Rc<Registry>,
+    _listener: registry::Listener,
+    _state: Rc<RefCell<State>>,
+}
+
+struct Headset {
+    name: Arc<str>,
+    /// Whether the headset currently uses a profile with its microphone.
+    active: Rc<Cell<bool>>,
+    _device: Device,
+    _listener: DeviceListener,
+}
+
+#[derive(Default)]
+struct State {
+    headsets: HashMap<u32, Headset>,
+}
+
+/// Call `on_change` with the headset's name and true whenever a Bluetooth
+/// headset switches to a profile that uses its microphone (HSP or HFP), and
+/// with false when it switches back (e.g. to A2DP) or disappears.
+pub fn watch(core: &Core, on_change: impl Fn(Arc<str>, bool) + 'static) -> Result<HeadsetWatcher> {
+    let registry = Rc::new(core.get_registry()?);
+    let state: Rc<RefCell<State>> = Rc::default();
+    let on_change = Rc::new(on_change);
+
+    let listener = registry
+        .add_listener_local()
+        .global({
+            let registry = Rc::downgrade(&registry);
+            let state = state.clone();
+            let on_change = on_change.clone();
+            move |global| {
+                if global.type_ != ObjectType::Device {
+                    return;
+                }
+                let Some(props) = global.props else {
+                    return;
+                };
+                if props.get(*keys::DEVICE_API) != Some("bluez5") {
+                    return;
+                }
+                let Some(registry) = registry.upgrade() else {
+                    return;
+                };
+                let device: Device = match registry.bind(global) {
+                    Ok(device) => device,
+                    Err(e) => {
+                        eprintln!("error: cannot bind Bluetooth device: {e}");
+                        return;
+                    }
+                };
+                let name: Arc<str> = props
+                    .get(*keys::DEVICE_DESCRIPTION)
+                    .or(props.get(*keys::DEVICE_NAME))
+                    .unwrap_or("A Bluetooth headset")
+                    .into();
+                let active: Rc<Cell<bool>> = Rc::default();
+                let listener = device
+                    .add_listener_local()
+                    .param({
+                        let name = name.clone();
+                        let active = active.clone();
+                        let on_change = on_change.clone();
+                        move |_, id, _, _, param| {
+                            if id != ParamType::Profile {
+                                return;
+                            }
+                            let Some(profile) = param.and_then(parse_profile_name) else {
+                                return;
+                            };
+                            let is_active = is_headset_profile(&profile);
+                            if active.replace(is_active) != is_active {
+                                on_change(name.clone(), is_active);
+                            }
+                        }
+                    })
+                    .register();
+                device.subscribe_params(&[ParamType::Profile]);
+                state.borrow_mut().headsets.insert(
+                    global.id,
+                    Headset {
+                        name,
+                        active,
+                        _device: device,
+                        _listener: listener,
+                    },
+                );
+            }
+        })
+        .global_remove({
+            let state = state.clone();
+            move |id| {
+                let Some(headset) = state.borrow_mut().headsets.remove(&id) else {
+                    return;
+                };
+                if headset.active.get() {
+                    on_change(headset.name.clone(), false);
+                }
+            }
+        })
+        .register();
+
+    Ok(HeadsetWatcher {
+        _registry: registry,
+        _listener: listener,
+        _state: state,
+    })
+}
+
+/// Get the name of the profile that a Profile param describes.
+fn parse_profile_name(param: &Pod) -> Option<String> {
+    let Ok((_, Value::Object(object))) = PodDeserializer::deserialize_any_from(param.as_bytes())
+    else {
+        return None;
+    };
+    object
+        .properties
+        .into_iter()
+        .find(|property| property.key == SPA_PARAM_PROFILE_name)
+        .and_then(|property| match property.value {
+            Value::String(name) => Some(name),
+            _ => None,
+        })
+}
+
+/// Check whether a profile of the bluez5 device monitor uses the headset's
+/// microphone, like "headset-head-unit" or "headset-head-unit-msbc".
+fn is_headset_profile(profile: &str) -> bool {
+    profile.starts_with("headset-head-unit")
+}
@@ -0,0 +1,236 @@
+//! The `calibrate` subcommand, which measures the noise floor and speech
+//! levels and suggests matching settings.
+
+use crate::capture::Capture;
+use crate::{connect_capture, negotiated_format, Args};
+use anyhow::Result;
+use pipewire::core::Core;
+use pipewire::keys;
+use pipewire::main_loop::MainLoop;
+use pipewire::properties::properties;
+use pipewire::stream::{Stream, StreamFlags};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// Minimum distance in dB between the noise floor and the suggested threshold.
+const MIN_MARGIN: f32 = 3.;
+/// Bounds for the suggested hold time.
+const HOLD_TIME_RANGE: (Duration, Duration) = (Duration::from_millis(250), Duration::from_secs(2));
+
+/// The measured levels, and the settings that match them.
+#[derive(Debug, Clone)]
+pub struct Suggestion {
+    /// The highest level during silence, in dB.
+    pub noise_floor: f32,
+    /// The median level during speech, in dB.
+    pub speech_median: f32,
+    /// The highest level during speech, in dB.
+    pub speech_peak: f32,
+    pub threshold: f32,
+    pub hold_time: Duration,
+}
+
+struct CalibrationState {
+    /// Reads the same channels and filters them like while monitoring, so
+    /// that the levels are on the same scale.
+    capture: Capture,
+    levels: Rc<RefCell<Vec<(Instant, f32)>>>,
+}
+
+pub fn calibrate(mainloop: &MainLoop, core: &Core, args: &Args, duration: Duration) -> Result<()> {
+    if args.window.is_zero() {
+        anyhow::bail!("--window must not be zero");
+    }
+    #[cfg(not(feature = "spectral"))]
+    if let crate::detector::DetectionMode::Spectral = args.detection_mode {
+        anyhow::bail!("spectral detection requires building with the \"spectral\" feature");
+    }
+    let levels: Rc<RefCell<Vec<(Instant, f32)>>> = Rc::default();
+
+    let mut props = properties! {
+        *keys::MEDIA_TYPE => "Audio",
+        *keys::MEDIA_CATEGORY => "Capture",
+        *keys::MEDIA_ROLE => "Accessibility",
+    };
+    // Calibration is done for a single source, use the first one if there are several.
+    if let Some(target) = args.targets.first() {
+        props.insert(*keys::TARGET_OBJECT, target.as_str());
+    }
+    if args.monitor_sink {
+        props.insert(*keys::STREAM_CAPTURE_SINK, "true");
+    }
+    let stream = Stream::new(core, "micclick-calibrate", props)?;
+    let state = CalibrationState {
+        capture: Capture::new(args),
+        levels: levels.clone(),
+    };
+    let _listener = stream
+        .add_local_listener_with_user_data(state)
+        .param_changed(|_, state, id, param| {
+            if let Some((format, info)) = negotiated_format(id, param) {
+                state.capture.set_format(format, &info);
+            }
+        })
+        .process(|stream, state| {
+            let Some(mut buffer) = stream.dequeue_buffer() else {
+                return;
+            };
+            state.capture.read(buffer.datas_mut());
+            while state.capture.window().is_some() {
+                state.capture.filter_window();
+                if let Some(measurement) = state.capture.measure() {
+                    state
+                        .levels
+                        .borrow_mut()
+                        .push((Instant::now(), measurement.level));
+                }
+                state.capture.advance();
+            }
+        })
+        .register()?;
+    connect_capture(&stream, StreamFlags::empty(), args, None)?;
+
+    let secs = duration.as_secs_f32();
+    println!("Please stay silent for {secs} seconds...");
+    let start = Instant::now();
+    let speaking = Cell::new(false);
+    let timer = mainloop.loop_().add_timer({
+        let mainloop = mainloop.clone();
+        move |_| {
+            if speaking.replace(true) {
+                mainloop.quit();
+            } else {
+                println!("Now please talk normally for {secs} seconds...");
+            }
+        }
+    });
+    timer
+        .update_timer(Some(duration), Some(duration))
+        .into_result()?;
+    mainloop.run();
+    stream.disconnect()?;
+
+    let levels = levels.borrow();
+    let (silence, speech): (Vec<_>, Vec<_>) = levels
+        .iter()
+        .map(|&(time, level)| {
+            let db = 20. * level.max(f32::MIN_POSITIVE).log10() + args.offset();
+            (time, db)
+        })
+        .partition(|&(time, _)| time < start + duration);
+    let suggestion = suggest(&silence, &speech)?;
+    println!("Noise floor: {:.1} dB", suggestion.noise_floor);
+    println!(
+        "Speech: {:.1} dB median, {:.1} dB peak",
+        suggestion.speech_median, suggestion.speech_peak
+    );
+    let threshold = suggestion.threshold;
+    let hold_time = suggestion.hold_time;
+
+    println!();
+    println!("Suggested settings:");
+    // With "=", the negative values are not taken for other flags.
+    // The threshold is on the scale of the offsets that were given.
+    let mut flags = String::new();
+    if args.db_offset != 0. {
+        flags += &format!("--db-offset={} ", args.db_offset);
+    }
+    if args.capture_gain != 0. {
+        flags += &format!("--capture-gain={} ", args.capture_gain);
+    }
+    println!(
+        "    {flags}--threshold={threshold:.0} --hold-time={}",
+        hold_time.as_millis()
+    );
+    Ok(())
+}
+
+/// Suggest a threshold and hold time from the levels in dB that were measured
+/// while the user stayed silent, and while they talked.
+pub fn suggest(silence: &[(Instant, f32)], speech: &[(Instant, f32)]) -> Result<Suggestion> {
+    if silence.is_empty() || speech.is_empty() {
+        anyhow::bail!("no audio was captured, is a microphone connected?");
+    }
+
+    let noise_floor = silence.iter().map(|&(_, db)| db).fold(f32::MIN, f32::max);
+    let mut speech_levels: Vec<f32> = speech.iter().map(|&(_, db)| db).collect();
+    speech_levels.sort_by(f32::total_cmp);
+    let speech_peak = speech_levels[speech_levels.len() - 1];
+    let speech_median = speech_levels[speech_levels.len() / 2];
+    if speech_median <= noise_floor + MIN_MARGIN {
+        anyhow::bail!(
+            "speech ({speech_median:.1} dB) was not clearly louder than the background noise \
+             ({noise_floor:.1} dB)"
+        );
+    }
+
+    let threshold = noise_floor + ((speech_median - noise_floor) / 3.).max(MIN_MARGIN);
+
+    // Use the longest pause between two words as hold time, so that the
+    // "on" state is held during normal speech.
+    let mut longest_pause = Duration::ZERO;
+    let mut last_active: Option<Instant> = None;
+    for &(time, _) in speech.iter().filter(|&&(_, db)| db > threshold) {
+        if let Some(last) = last_active {
+            longest_pause = longest_pause.max(time - last);
+        }
+        last_active = Some(time);
+    }
+    Ok(Suggestion {
+        noise_floor,
+        speech_median,
+        speech_peak,
+        threshold,
+        hold_time: longest_pause.clamp(HOLD_TIME_RANGE.0, HOLD_TIME_RANGE.1),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Levels in dB, one every 100 ms from `start`.
+    fn levels(start: Instant, levels: &[f32]) -> Vec<(Instant, f32)> {
+        (0..)
+            .map(|n| start + Duration::from_millis(100) * n)
+            .zip(levels.iter().copied())
+            .collect()
+    }
+
+    #[test]
+    fn suggest_threshold_between_noise_and_speech() {
+        let start = Instant::now();
+        let silence = levels(start, &[-70., -62., -65.]);
+        // Two words with a pause of 500 ms in between.
+        let speech = levels(
+            start,
+            &[-30., -30., -80., -80., -80., -80., -30., -20., -30.],
+        );
+        let suggestion = suggest(&silence, &speech).unwrap();
+        assert_eq!(suggestion.noise_floor, -62.);
+        assert_eq!(suggestion.speech_median, -30.);
+        assert_eq!(suggestion.speech_peak, -20.);
+        assert!((suggestion.threshold - (-62. + 32. / 3.)).abs() < 1e-4);
+        assert_eq!(suggestion.hold_time, Duration::from_millis(500));
+    }
+
+    #[test]
+    fn hold_time_is_clamped() {
+        let start = Instant::now();
+        let silence = levels(start, &[-60.]);
+        let speech = levels(start, &[-20.; 10]);
+        let suggestion = suggest(&silence, &speech).unwrap();
+        assert_eq!(suggestion.hold_time, HOLD_TIME_RANGE.0);
+    }
+
+    #[test]
+    fn speech_must_be_louder_than_the_noise() {
+        let start = Instant::now();
+        assert!(suggest(&[], &levels(start, &[-20.])).is_err());
+        assert!(suggest(&levels(start, &[-60.]), &[]).is_err());
+        let silence = levels(start, &[-40.]);
+        let speech = levels(start, &[-39., -38., -20.]);
+        assert!(suggest(&silence, &speech).is_err());
+    }
+}
@@ -0,0 +1,203 @@
+//! Reading captured buffers into analysis windows, with the selected channels
+//! and the configured filters, so that monitoring and the calibrate subcommand
+//! measure the same signal.
+
+use crate::detector::{self, DetectionMode, Detector, Measurement};
+use crate::filter::{self, Biquad};
+use crate::format::{self, SampleFormat};
+use crate::{Args, VOICE_BAND};
+use libspa::buffer::Data;
+use libspa::param::audio::AudioInfoRaw;
+use std::time::Duration;
+
+pub struct Capture {
+    detection_mode: DetectionMode,
+    window_duration: Duration,
+    /// The channels given by --channels, or empty for all of them.
+    channels: Vec<u32>,
+    dc_block: bool,
+    highpass: Option<f32>,
+    voice_band: bool,
+    a_weighting: bool,
+    format: Option<SampleFormat>,
+    rate: u32,
+    n_channels: u32,
+    planar: bool,
+    n_selected_channels: u32,
+    samples: Vec<f32>,
+    /// The chain of filters for each selected channel.
+    filters: Vec<Vec<Biquad>>,
+    detector: Option<Box<dyn Detector>>,
+    /// The interleaved samples of the selected channels that were not analyzed yet.
+    window: Vec<f32>,
+    window_len: usize,
+}
+
+impl Capture {
+    pub fn new(args: &Args) -> Self {
+        Self {
+            detection_mode: args.detection_mode,
+            window_duration: args.window,
+            channels: args.channels.clone(),
+            dc_block: args.dc_block,
+            highpass: args.highpass,
+            voice_band: args.voice_band,
+            a_weighting: args.a_weighting,
+            format: None,
+            rate: 0,
+            n_channels: 0,
+            planar: false,
+            n_selected_channels: 0,
+            samples: Vec::new(),
+            filters: Vec::new(),
+            detector: None,
+            window: Vec::new(),
+            window_len: 0,
+        }
+    }
+
+    /// Set up the channel selection, the filters and the detector for a newly
+    /// negotiated format.
+    pub fn set_format(&mut self, format: SampleFormat, info: &AudioInfoRaw) {
+        self.format = Some(format);
+        self.planar = SampleFormat::is_planar(info.as_raw().format);
+        self.n_channels = info.channels();
+        self.n_selected_channels = self.n_channels;
+        if !self.channels.is_empty() {
+            self.n_selected_channels = 0;
+            for &channel in self.channels.iter() {
+                if channel < self.n_channels {
+                    self.n_selected_channels += 1;
+                } else {
+                    eprintln!(
+                        "warning: source only has {} channels, ignoring channel {channel}",
+                        self.n_channels
+                    );
+                }
+            }
+        }
+        let rate = info.rate();
+        self.rate = rate;
+        let window_frames = (rate as f32 * self.window_duration.as_secs_f32()).round() as usize;
+        self.window_len = window_frames * self.n_selected_channels as usize;
+        self.window.clear();
+        let mut filters = Vec::new();
+        if self.dc_block {
+            filters.push(Biquad::dc_blocker(rate));
+        }
+        if let Some(cutoff) = self.highpass {
+            filters.push(Biquad::highpass(rate, cutoff));
+        }
+        if self.voice_band {
+            filters.push(Biquad::highpass(rate, VOICE_BAND.0));
+            filters.push(Biquad::lowpass(rate, VOICE_BAND.1));
+        }
+        if self.a_weighting {
+            filters.extend(filter::a_weighting(rate));
+        }
+        self.filters = vec![filters; self.n_selected_channels as usize];
+        self.detector = Some(detector::create(
+            self.detection_mode,
+            rate,
+            self.n_selected_channels,
+            self.window_duration,
+        ));
+    }
+
+    /// Get how long the samples in a buffer play, or None before the format
+    /// is known.
+    pub fn buffer_duration(&self, datas: &mut [Data]) -> Option<Duration> {
+        let format = self.format?;
+        let samples_per_frame = if self.planar { 1 } else { self.n_channels };
+        let data = datas.first_mut()?;
+        let frame_size = format.sample_size() * samples_per_frame.max(1) as usize;
+        let frames = data.chunk().size() as usize / frame_size;
+        Some(Duration::from_secs_f64(
+            frames as f64 / self.rate.max(1) as f64,
+        ))
+    }
+
+    /// Append the samples of the selected channels in a buffer to the window.
+    pub fn read(&mut self, datas: &mut [Data]) {
+        let Some(format) = self.format else {
+            return;
+        };
+        let all_channels = self.channels.is_empty();
+        // Interleaved formats only use the first plane, any further ones are ignored.
+        let n_planes = if self.planar { datas.len() } else { 1 };
+        if self.planar && n_planes < self.n_channels as usize {
+            eprintln!(
+                "warning: skipping buffer with {n_planes} planes for {} channels",
+                self.n_channels
+            );
+            return;
+        }
+        // Planes of different lengths are cut to the shortest one, so that they can be interleaved.
+        let plane_len = datas
+            .iter_mut()
+            .take(n_planes)
+            .map(|data| data.chunk().size() as usize / format.sample_size())
+            .min()
+            .unwrap_or(0);
+        self.samples.clear();
+        for (plane, data) in datas.iter_mut().take(n_planes).enumerate() {
+            if self.planar && !all_channels && !self.channels.contains(&(plane as u32)) {
+                continue;
+            }
+            let start = self.samples.len();
+            format.read_samples(data, &mut self.samples);
+            if self.planar {
+                self.samples.truncate(start + plane_len);
+            }
+        }
+        if !self.planar && !all_channels && self.n_channels > 0 {
+            let mut n = 0;
+            self.samples.retain(|_| {
+                let channel = n % self.n_channels;
+                n += 1;
+                self.channels.contains(&channel)
+            });
+        }
+        if self.window_len == 0 {
+            return;
+        }
+
+        if self.planar {
+            let n_channels = self.n_selected_channels as usize;
+            format::interleave(&self.samples, n_channels, &mut self.window);
+        } else {
+            self.window.extend_from_slice(&self.samples);
+        }
+    }
+
+    /// Get the next full analysis window, before it is filtered, or None if
+    /// more samples are needed.
+    pub fn window(&self) -> Option<&[f32]> {
+        (self.window_len > 0 && self.window.len() >= self.window_len)
+            .then(|| &self.window[..self.window_len])
+    }
+
+    /// Run the filters over the next full analysis window, and get it.
+    pub fn filter_window(&mut self) -> &mut [f32] {
+        let window = &mut self.window[..self.window_len];
+        for frame in window.chunks_exact_mut(self.filters.len()) {
+            for (sample, filters) in frame.iter_mut().zip(self.filters.iter_mut()) {
+                for filter in filters.iter_mut() {
+                    *sample = filter.process(*sample);
+                }
+            }
+        }
+        window
+    }
+
+    /// Measure the next full analysis window with the configured detector.
+    pub fn measure(&mut self) -> Option<Measurement> {
+        let detector = self.detector.as_mut()?;
+        Some(detector.measure(&mut self.window[..self.window_len]))
+    }
+
+    /// Drop the next full analysis window, once it was analyzed.
+    pub fn advance(&mut self) {
+        self.window.drain(..self.window_len);
+    }
+}
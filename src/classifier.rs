@@ -0,0 +1,103 @@
+//! A heuristic classifier that tells speech apart from other kinds of sounds,
+//! based on the envelope of the signal.
+
+use crate::scan;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// How far back the envelope is considered to detect steady noise.
+const HISTORY: Duration = Duration::from_secs(1);
+/// Windows with a higher ratio between peak and RMS level are considered impulsive.
+const IMPULSIVE_CREST_FACTOR: f32 = 6.;
+/// If the level varies by less than this many dB (standard deviation) over the
+/// history, the sound is considered steady.
+const STEADY_DEVIATION: f32 = 3.;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SoundClass {
+    /// A modulated sound with moderate peaks, like speech.
+    Speech,
+    /// A short, sharp sound, like a keyboard click.
+    Impulsive,
+    /// A sound with a steady level, like a fan.
+    Steady,
+}
+
+#[derive(Debug)]
+pub struct Classifier {
+    levels: VecDeque<f32>,
+    n_windows: usize,
+}
+
+impl Classifier {
+    /// Create a new classifier, which will be fed with analysis windows of the given length.
+    pub fn new(window: Duration) -> Self {
+        let n_windows = ((HISTORY.as_secs_f32() / window.as_secs_f32()).ceil() as usize).max(2);
+        Self {
+            levels: VecDeque::with_capacity(n_windows),
+            n_windows,
+        }
+    }
+
+    /// Classify one analysis window of samples.
+    pub fn classify(&mut self, samples: &[f32]) -> SoundClass {
+        let (peak, power) = scan::peak_and_power(samples);
+        let rms = (power / samples.len().max(1) as f32).sqrt();
+        let db = 20. * rms.max(f32::MIN_POSITIVE).log10();
+
+        if self.levels.len() == self.n_windows {
+            self.levels.pop_front();
+        }
+        self.levels.push_back(db);
+
+        if peak > rms * IMPULSIVE_CREST_FACTOR {
+            return SoundClass::Impulsive;
+        }
+        if self.levels.len() == self.n_windows {
+            let n = self.levels.len() as f32;
+            let mean = self.levels.iter().sum::<f32>() / n;
+            let variance = self.levels.iter().map(|l| (l - mean).powi(2)).sum::<f32>() / n;
+            if variance.sqrt() < STEADY_DEVIATION {
+                return SoundClass::Steady;
+            }
+        }
+        SoundClass::Speech
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WINDOW: Duration = Duration::from_millis(100);
+
+    #[test]
+    fn click_is_impulsive() {
+        let mut classifier = Classifier::new(WINDOW);
+        let mut samples = vec![0.001; 4800];
+        samples[100] = 0.8;
+        assert_eq!(classifier.classify(&samples), SoundClass::Impulsive);
+    }
+
+    #[test]
+    fn constant_level_is_steady_after_the_history() {
+        let mut classifier = Classifier::new(WINDOW);
+        let samples = vec![0.1; 4800];
+        let classes: Vec<_> = (0..classifier.n_windows)
+            .map(|_| classifier.classify(&samples))
+            .collect();
+        // Until there is enough history, the sound is not known to be steady.
+        assert_eq!(classes[0], SoundClass::Speech);
+        assert_eq!(classes.last(), Some(&SoundClass::Steady));
+    }
+
+    #[test]
+    fn modulated_level_is_speech() {
+        let mut classifier = Classifier::new(WINDOW);
+        for n in 0..classifier.n_windows * 2 {
+            let level = if n % 2 == 0 { 0.2 } else { 0.01 };
+            let samples = vec![level; 4800];
+            assert_eq!(classifier.classify(&samples), SoundClass::Speech);
+        }
+    }
+}
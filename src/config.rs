@@ -0,0 +1,151 @@
+//! The configuration file, for settings that do not fit on the command line.
+
+use crate::schedule::{QuietHours, ScheduleEntry};
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// The threshold in dB, unless given on the command line.
+    pub threshold: Option<f32>,
+    /// The hold time in milliseconds, unless given on the command line.
+    pub hold_time: Option<u64>,
+    /// The on sound, unless given on the command line.
+    pub on_sound: Option<String>,
+    /// The off sound, unless given on the command line.
+    pub off_sound: Option<String>,
+    /// The source to monitor, unless given on the command line.
+    pub target: Option<String>,
+    /// Times of day that use different thresholds or sounds.
+    pub schedule: Vec<ScheduleEntry>,
+    /// Times of day without any sounds, which apply on top of the schedule.
+    pub quiet_hours: Vec<QuietHours>,
+    /// The sound to play when the capture stream is suspended, unless given on the command line.
+    pub suspended_sound: Option<String>,
+    /// Sounds for individual sources, by their `--target`.
+    pub sources: HashMap<String, SourceSounds>,
+    /// Tray icon names, unless given on the command line.
+    pub icons: Icons,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Icons {
+    pub active: Option<String>,
+    pub inactive: Option<String>,
+    pub suspended: Option<String>,
+}
+
+/// The sounds of one source, which replace the global on and off sounds.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SourceSounds {
+    pub on_sound: Option<String>,
+    pub off_sound: Option<String>,
+}
+
+/// The default location of the configuration file.
+pub fn default_path() -> PathBuf {
+    glib::user_config_dir()
+        .join("pw-micclick")
+        .join("config.toml")
+}
+
+/// Load the configuration from the given file, or from the default location.
+///
+/// It is not an error if there is no file at the default location.
+pub fn load(path: Option<&Path>) -> Result<Config> {
+    let default_path = default_path();
+    let contents = match path {
+        Some(path) => std::fs::read_to_string(path)
+            .with_context(|| format!("cannot read config file {}", path.display()))?,
+        None => match std::fs::read_to_string(&default_path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Config::default()),
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("cannot read config file {}", default_path.display()))
+            }
+        },
+    };
+    let path = path.unwrap_or(&default_path);
+    let config: Config = toml::from_str(&contents)
+        .with_context(|| format!("invalid config file {}", path.display()))?;
+    for entry in config.schedule.iter() {
+        if let (Some(on), Some(off)) = (entry.threshold_on(), entry.threshold_off()) {
+            if off > on {
+                anyhow::bail!(
+                    "invalid config file {}: threshold_off must not be higher than threshold_on",
+                    path.display()
+                );
+            }
+        }
+    }
+    Ok(config)
+}
+
+/// The settings that the settings window changes.
+#[derive(Debug, Clone)]
+pub struct Settings {
+    pub threshold: f32,
+    pub hold_time: Duration,
+    pub on_sound: Option<String>,
+    pub off_sound: Option<String>,
+    pub target: Option<String>,
+}
+
+/// Write the settings to the given config file, or to the default location.
+///
+/// Everything else in the file is kept, although comments are lost.
+pub fn save(path: Option<&Path>, settings: &Settings) -> Result<()> {
+    let default_path = default_path();
+    let path = path.unwrap_or(&default_path);
+    let mut table: toml::Table = match std::fs::read_to_string(path) {
+        Ok(contents) => toml::from_str(&contents)
+            .with_context(|| format!("invalid config file {}", path.display()))?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => toml::Table::new(),
+        Err(e) => {
+            return Err(e).with_context(|| format!("cannot read config file {}", path.display()))
+        }
+    };
+    table.insert("threshold".to_owned(), settings.threshold.into());
+    table.insert(
+        "hold_time".to_owned(),
+        (settings.hold_time.as_millis() as i64).into(),
+    );
+    for (key, value) in [
+        ("on_sound", &settings.on_sound),
+        ("off_sound", &settings.off_sound),
+        ("target", &settings.target),
+    ] {
+        match value {
+            Some(value) => table.insert(key.to_owned(), value.as_str().into()),
+            None => table.remove(key),
+        };
+    }
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("cannot create directory {}", dir.display()))?;
+    }
+    std::fs::write(path, toml::to_string(&table)?)
+        .with_context(|| format!("cannot write config file {}", path.display()))
+}
+
+/// Create an empty config file at the given path, or at the default location,
+/// unless there already is one.
+pub fn create(path: Option<&Path>) -> Result<()> {
+    let default_path = default_path();
+    let path = path.unwrap_or(&default_path);
+    if path.exists() {
+        return Ok(());
+    }
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("cannot create directory {}", dir.display()))?;
+    }
+    std::fs::write(path, "").with_context(|| format!("cannot write config file {}", path.display()))
+}
@@ -0,0 +1,77 @@
+//! A D-Bus interface on the session bus, for controlling pw-micclick from
+//! scripts and keyboard shortcuts, e.g. with
+//! `busctl --user call io.github.Wuestengecko.PwMicclick /io/github/Wuestengecko/PwMicclick io.github.Wuestengecko.PwMicclick ToggleSilenced`.
+
+use crate::Request;
+use anyhow::{Context, Result};
+use gio::{BusNameOwnerFlags, BusType, DBusNodeInfo};
+
+const NAME: &str = "io.github.Wuestengecko.PwMicclick";
+const PATH: &str = "/io/github/Wuestengecko/PwMicclick";
+
+const INTROSPECTION: &str = r#"
+<node>
+  <interface name="io.github.Wuestengecko.PwMicclick">
+    <method name="TogglePause"/>
+    <method name="ToggleSilenced"/>
+  </interface>
+</node>
+"#;
+
+/// Offer the control interface, and pass the calls on as requests until the
+/// process exits.
+pub fn thread_main(request_sender: pipewire::channel::Sender<Request>) {
+    if let Err(e) = serve(request_sender) {
+        eprintln!("error: cannot offer the D-Bus control interface: {e:#}");
+    }
+}
+
+fn serve(request_sender: pipewire::channel::Sender<Request>) -> Result<()> {
+    let bus = gio::bus_get_sync(BusType::Session, None::<&gio::Cancellable>)
+        .context("cannot connect to the session bus")?;
+    let node = DBusNodeInfo::for_xml(INTROSPECTION)?;
+    let interface = node
+        .lookup_interface(NAME)
+        .context("the interface is missing from its description")?;
+    let context = glib::MainContext::new();
+    context
+        .with_thread_default(|| -> Result<()> {
+            // Method calls are dispatched in the thread-default context of
+            // the registration, so register on this thread's own context.
+            let _registration = bus.register_object(
+                PATH,
+                &interface,
+                move |_, _, _, _, method, _, invocation| {
+                    let request = match method {
+                        "TogglePause" => Request::TogglePause,
+                        "ToggleSilenced" => Request::ToggleSilenced,
+                        _ => {
+                            invocation.return_dbus_error(
+                                "org.freedesktop.DBus.Error.UnknownMethod",
+                                &format!("unknown method {method}"),
+                            );
+                            return;
+                        }
+                    };
+                    let _ = request_sender.send(request);
+                    invocation.return_value(None);
+                },
+                |_, _, _, _, _| unreachable!("the interface has no properties"),
+                |_, _, _, _, _, _| unreachable!("the interface has no properties"),
+            )?;
+            let _name = gio::bus_own_name_on_connection(
+                &bus,
+                NAME,
+                BusNameOwnerFlags::NONE,
+                |_, _| {},
+                |_, name| {
+                    eprintln!(
+                        "error: cannot own the D-Bus name {name}, is pw-micclick already running?"
+                    )
+                },
+            );
+            glib::MainLoop::new(Some(&context), false).run();
+            Ok(())
+        })
+        .context("cannot use the main context")?
+}
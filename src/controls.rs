@@ -0,0 +1,114 @@
+//! Settings that can change while running, shared between the threads.
+
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+pub struct Controls {
+    /// The linear level that turns the "on" state on, stored as f32 bits.
+    threshold_on: AtomicU32,
+    /// The linear level that keeps the "on" state on, stored as f32 bits.
+    threshold_off: AtomicU32,
+    /// An adjustment in dB that the user made to both thresholds, stored as f32 bits.
+    threshold_adjustment: AtomicU32,
+    /// How long the "on" state is held after the input stopped, in microseconds.
+    hold_time: AtomicU64,
+    /// Whether sounds are played at all.
+    sounds: AtomicBool,
+    /// Whether the user muted all sounds, regardless of the schedule.
+    silenced: AtomicBool,
+    /// Whether monitoring is paused, e.g. while the session is locked.
+    paused: AtomicBool,
+    /// The reference point for `suppressed_until`.
+    epoch: Instant,
+    /// Until when the input is ignored, in microseconds since `epoch`.
+    suppressed_until: AtomicU64,
+}
+
+impl Controls {
+    pub fn new(threshold_on: f32, threshold_off: f32, hold_time: Duration) -> Self {
+        Self {
+            threshold_on: AtomicU32::new(threshold_on.to_bits()),
+            threshold_off: AtomicU32::new(threshold_off.to_bits()),
+            threshold_adjustment: AtomicU32::new(0f32.to_bits()),
+            hold_time: AtomicU64::new(hold_time.as_micros() as u64),
+            sounds: AtomicBool::new(true),
+            silenced: AtomicBool::new(false),
+            paused: AtomicBool::new(false),
+            epoch: Instant::now(),
+            suppressed_until: AtomicU64::new(0),
+        }
+    }
+
+    /// Get the linear on and off thresholds, including the user's adjustment.
+    pub fn thresholds(&self) -> (f32, f32) {
+        let factor = 10f32.powf(self.threshold_adjustment() / 20.);
+        (
+            f32::from_bits(self.threshold_on.load(Ordering::Relaxed)) * factor,
+            f32::from_bits(self.threshold_off.load(Ordering::Relaxed)) * factor,
+        )
+    }
+
+    pub fn set_thresholds(&self, threshold_on: f32, threshold_off: f32) {
+        self.threshold_on
+            .store(threshold_on.to_bits(), Ordering::Relaxed);
+        self.threshold_off
+            .store(threshold_off.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Get the adjustment in dB that the user made to the thresholds, e.g. from the tray.
+    ///
+    /// It applies on top of the thresholds from the command line or the schedule.
+    pub fn threshold_adjustment(&self) -> f32 {
+        f32::from_bits(self.threshold_adjustment.load(Ordering::Relaxed))
+    }
+
+    pub fn set_threshold_adjustment(&self, db: f32) {
+        self.threshold_adjustment
+            .store(db.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn hold_time(&self) -> Duration {
+        Duration::from_micros(self.hold_time.load(Ordering::Relaxed))
+    }
+
+    pub fn set_hold_time(&self, hold_time: Duration) {
+        self.hold_time
+            .store(hold_time.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    pub fn sounds(&self) -> bool {
+        self.sounds.load(Ordering::Relaxed)
+    }
+
+    pub fn set_sounds(&self, sounds: bool) {
+        self.sounds.store(sounds, Ordering::Relaxed);
+    }
+
+    pub fn silenced(&self) -> bool {
+        self.silenced.load(Ordering::Relaxed)
+    }
+
+    /// Toggle whether the user muted all sounds, and return the new state.
+    pub fn toggle_silenced(&self) -> bool {
+        !self.silenced.fetch_xor(true, Ordering::Relaxed)
+    }
+
+    pub fn paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::Relaxed);
+    }
+
+    /// Ignore the input for the given time from now on, e.g. while a sound is
+    /// playing that the microphone might pick up.
+    pub fn suppress_input(&self, duration: Duration) {
+        let until = (self.epoch.elapsed() + duration).as_micros() as u64;
+        self.suppressed_until.fetch_max(until, Ordering::Relaxed);
+    }
+
+    pub fn input_suppressed(&self) -> bool {
+        (self.epoch.elapsed().as_micros() as u64) < self.suppressed_until.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,123 @@
+//! Detection of ongoing conversations, as opposed to single utterances.
+
+use std::time::Duration;
+
+#[derive(Debug)]
+pub struct Conversation {
+    /// How much speech makes up a conversation.
+    min_speech: Duration,
+    /// How long a pause may be before the conversation is over.
+    max_gap: Duration,
+    /// The length of one analysis window.
+    window: Duration,
+    speech: Duration,
+    last_speech: Option<Duration>,
+    active: bool,
+}
+
+impl Conversation {
+    pub fn new(min_speech: Duration, max_gap: Duration, window: Duration) -> Self {
+        Self {
+            min_speech,
+            max_gap,
+            window,
+            speech: Duration::ZERO,
+            last_speech: None,
+            active: false,
+        }
+    }
+
+    /// Update the state with one analysis window.
+    ///
+    /// Returns whether a conversation started (true) or ended (false), or
+    /// None if nothing changed.
+    pub fn update(&mut self, now: Duration, speaking: bool) -> Option<bool> {
+        let gap_exceeded = self
+            .last_speech
+            .is_some_and(|last| now - last > self.max_gap);
+        if speaking {
+            if gap_exceeded {
+                self.speech = Duration::ZERO;
+            }
+            self.last_speech = Some(now);
+            self.speech += self.window;
+            if !self.active && self.speech >= self.min_speech {
+                self.active = true;
+                return Some(true);
+            }
+        } else if gap_exceeded {
+            let was_active = self.active;
+            self.reset();
+            if was_active {
+                return Some(false);
+            }
+        }
+        None
+    }
+
+    /// Forget about any ongoing conversation, e.g. because the stream was paused.
+    pub fn reset(&mut self) {
+        self.speech = Duration::ZERO;
+        self.last_speech = None;
+        self.active = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WINDOW: Duration = Duration::from_millis(100);
+
+    fn conversation() -> Conversation {
+        Conversation::new(Duration::from_millis(300), Duration::from_secs(1), WINDOW)
+    }
+
+    /// Feed consecutive windows starting at `start`, and collect the changes.
+    fn feed(conversation: &mut Conversation, start: u32, speaking: &[bool]) -> Vec<bool> {
+        (start..)
+            .zip(speaking)
+            .filter_map(|(n, &speaking)| conversation.update(WINDOW * n, speaking))
+            .collect()
+    }
+
+    #[test]
+    fn starts_after_enough_speech() {
+        let mut conversation = conversation();
+        assert_eq!(feed(&mut conversation, 0, &[true, true]), []);
+        assert_eq!(feed(&mut conversation, 2, &[true]), [true]);
+        assert_eq!(feed(&mut conversation, 3, &[true]), []);
+    }
+
+    #[test]
+    fn short_pauses_count_towards_the_speech() {
+        let mut conversation = conversation();
+        let speaking = [true, false, false, true, false, true];
+        assert_eq!(feed(&mut conversation, 0, &speaking), [true]);
+    }
+
+    #[test]
+    fn long_pause_ends_it() {
+        let mut conversation = conversation();
+        feed(&mut conversation, 0, &[true; 3]);
+        // The last speech was at window 2, so the gap is exceeded at window 13.
+        assert_eq!(feed(&mut conversation, 3, &[false; 10]), []);
+        assert_eq!(feed(&mut conversation, 13, &[false]), [false]);
+    }
+
+    #[test]
+    fn long_pause_forgets_single_utterances() {
+        let mut conversation = conversation();
+        feed(&mut conversation, 0, &[true; 2]);
+        assert_eq!(feed(&mut conversation, 20, &[true; 2]), []);
+        assert_eq!(feed(&mut conversation, 22, &[true]), [true]);
+    }
+
+    #[test]
+    fn reset_forgets_the_speech() {
+        let mut conversation = conversation();
+        feed(&mut conversation, 0, &[true; 2]);
+        conversation.reset();
+        assert_eq!(feed(&mut conversation, 2, &[true; 2]), []);
+    }
+}
@@ -0,0 +1,85 @@
+//! Watching the PipeWire metadata for changes of the default source or sink.
+
+use anyhow::Result;
+use pipewire::core::Core;
+use pipewire::metadata::{Metadata, MetadataListener};
+use pipewire::registry::{self, Registry};
+use pipewire::types::ObjectType;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// The metadata key that holds the default source.
+const DEFAULT_SOURCE_KEY: &str = "default.audio.source";
+/// The metadata key that holds the default sink.
+const DEFAULT_SINK_KEY: &str = "default.audio.sink";
+
+/// Keeps the registry and metadata listeners alive.
+pub struct DefaultSourceWatcher {
+    _registry: Rc<Registry>,
+    _listener: registry::Listener,
+    _metadata: Rc<RefCell<Option<(Metadata, MetadataListener)>>>,
+}
+
+/// Call `on_change` whenever the default source (or sink, if `sink` is set) changes.
+///
+/// The default that is known when starting to watch does not count as a change.
+pub fn watch(
+    core: &Core,
+    sink: bool,
+    on_change: impl Fn() + 'static,
+) -> Result<DefaultSourceWatcher> {
+    let key = if sink {
+        DEFAULT_SINK_KEY
+    } else {
+        DEFAULT_SOURCE_KEY
+    };
+    let registry = Rc::new(core.get_registry()?);
+    let metadata: Rc<RefCell<Option<(Metadata, MetadataListener)>>> = Rc::default();
+    let on_change = Rc::new(on_change);
+    let listener = registry
+        .add_listener_local()
+        .global({
+            let registry = Rc::downgrade(&registry);
+            let metadata = metadata.clone();
+            move |global| {
+                if global.type_ != ObjectType::Metadata
+                    || global.props.and_then(|p| p.get("metadata.name")) != Some("default")
+                {
+                    return;
+                }
+                let Some(registry) = registry.upgrade() else {
+                    return;
+                };
+                let proxy: Metadata = match registry.bind(global) {
+                    Ok(proxy) => proxy,
+                    Err(e) => {
+                        eprintln!("error: cannot bind default metadata: {e}");
+                        return;
+                    }
+                };
+                let current: RefCell<Option<String>> = RefCell::new(None);
+                let on_change = on_change.clone();
+                let listener = proxy
+                    .add_listener_local()
+                    .property(move |subject, property, _, value| {
+                        if subject != 0 || property != Some(key) {
+                            return 0;
+                        }
+                        let value = value.map(str::to_owned);
+                        let previous = current.replace(value.clone());
+                        if previous.is_some() && previous != value {
+                            on_change();
+                        }
+                        0
+                    })
+                    .register();
+                *metadata.borrow_mut() = Some((proxy, listener));
+            }
+        })
+        .register();
+    Ok(DefaultSourceWatcher {
+        _registry: registry,
+        _listener: listener,
+        _metadata: metadata,
+    })
+}
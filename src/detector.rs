@@ -0,0 +1,169 @@
+//! Algorithms that measure the input level of an analysis window.
+
+use crate::loudness::MomentaryLoudness;
+use crate::scan;
+use crate::vad::Vad;
+use std::time::Duration;
+
+#[derive(Debug, Copy, Clone, clap::ValueEnum)]
+pub enum DetectionMode {
+    /// Use the highest absolute sample value.
+    Peak,
+    /// Use the root mean square of all samples, which is less sensitive to
+    /// single-sample spikes.
+    Rms,
+    /// Use the RMS level, but only for windows that a simple voice activity
+    /// detector classifies as speech.
+    Vad,
+    /// Use the EBU R128 momentary loudness, so that thresholds are given in LUFS.
+    Lufs,
+    /// Use the RMS level within the speech band, discounting tonal noises like
+    /// fans or hum. Requires the "spectral" feature.
+    Spectral,
+}
+
+/// The result of analyzing one window of samples.
+#[derive(Debug, Copy, Clone)]
+pub struct Measurement {
+    /// The measured level, as a linear amplitude.
+    pub level: f32,
+    /// Whether the window may contain speech. Detectors that cannot tell
+    /// speech apart from other noises always set this.
+    pub speech: bool,
+}
+
+impl Measurement {
+    fn level(level: f32) -> Self {
+        Self {
+            level,
+            speech: true,
+        }
+    }
+}
+
+pub trait Detector {
+    /// Measure one analysis window of interleaved samples.
+    ///
+    /// The detector may modify the samples in the process.
+    fn measure(&mut self, samples: &mut [f32]) -> Measurement;
+}
+
+/// Create the detector for the given mode and negotiated stream format.
+pub fn create(
+    mode: DetectionMode,
+    rate: u32,
+    n_channels: u32,
+    window: Duration,
+) -> Box<dyn Detector> {
+    match mode {
+        DetectionMode::Peak => Box::new(Peak),
+        DetectionMode::Rms => Box::new(Rms::new(n_channels)),
+        DetectionMode::Vad => Box::new(VoiceActivity {
+            rms: Rms::new(n_channels),
+            vad: Vad::default(),
+            window,
+        }),
+        DetectionMode::Lufs => Box::new(Lufs(MomentaryLoudness::new(rate, n_channels, window))),
+        #[cfg(feature = "spectral")]
+        DetectionMode::Spectral => {
+            let frames = (rate as f32 * window.as_secs_f32()).round() as usize;
+            Box::new(crate::spectral::Spectral::new(rate, n_channels, frames))
+        }
+        #[cfg(not(feature = "spectral"))]
+        DetectionMode::Spectral => unreachable!("spectral detection is not available"),
+    }
+}
+
+pub struct Peak;
+
+impl Detector for Peak {
+    fn measure(&mut self, samples: &mut [f32]) -> Measurement {
+        let (peak, _) = scan::peak_and_power(samples);
+        Measurement::level(peak)
+    }
+}
+
+/// Measures the RMS level of the loudest channel.
+pub struct Rms {
+    powers: Vec<f32>,
+}
+
+impl Rms {
+    pub fn new(n_channels: u32) -> Self {
+        Self {
+            powers: vec![0.; n_channels.max(1) as usize],
+        }
+    }
+}
+
+impl Detector for Rms {
+    fn measure(&mut self, samples: &mut [f32]) -> Measurement {
+        let n_frames = samples.len() / self.powers.len();
+        let power = if self.powers.len() == 1 {
+            scan::peak_and_power(samples).1
+        } else {
+            scan::channel_powers(samples, &mut self.powers);
+            self.powers.iter().copied().fold(0., f32::max)
+        };
+        Measurement::level((power / n_frames as f32).sqrt())
+    }
+}
+
+pub struct VoiceActivity {
+    rms: Rms,
+    vad: Vad,
+    window: Duration,
+}
+
+impl Detector for VoiceActivity {
+    fn measure(&mut self, samples: &mut [f32]) -> Measurement {
+        let Measurement { level: rms, .. } = self.rms.measure(samples);
+        let n_channels = self.rms.powers.len();
+        let zcr = scan::zero_crossings(samples, n_channels) as f32 / self.window.as_secs_f32();
+        Measurement {
+            level: rms,
+            speech: self.vad.process(rms, zcr),
+        }
+    }
+}
+
+pub struct Lufs(MomentaryLoudness);
+
+impl Detector for Lufs {
+    fn measure(&mut self, samples: &mut [f32]) -> Measurement {
+        Measurement::level(self.0.process(samples))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn peak_is_the_highest_absolute_sample() {
+        let mut samples = [0.1, -0.8, 0.5, 0.];
+        assert_eq!(Peak.measure(&mut samples).level, 0.8);
+    }
+
+    #[test]
+    fn rms_of_a_constant_signal_is_its_amplitude() {
+        let mut samples = [-0.5; 64];
+        let level = Rms::new(1).measure(&mut samples).level;
+        assert!((level - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn rms_uses_the_loudest_channel() {
+        // The left channel is silent, the right one at full scale.
+        let mut samples: Vec<f32> = [0., 1.].repeat(32);
+        let level = Rms::new(2).measure(&mut samples).level;
+        assert!((level - 1.).abs() < 1e-6);
+    }
+
+    #[test]
+    fn level_detectors_cannot_tell_speech() {
+        let mut samples = [0.; 16];
+        assert!(Peak.measure(&mut samples).speech);
+        assert!(Rms::new(1).measure(&mut samples).speech);
+    }
+}
@@ -0,0 +1,58 @@
+//! Icons that are built into the binary, for desktops whose icon theme lacks
+//! the symbolic icons that the tray uses.
+
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+const MIC_ON: &str = include_str!("../icons/mic-on.svg");
+const MIC_MEDIUM: &str = include_str!("../icons/mic-medium.svg");
+const MIC_OFF: &str = include_str!("../icons/mic-off.svg");
+const MIC_MUTED: &str = include_str!("../icons/mic-muted.svg");
+const WARNING: &str = include_str!("../icons/warning.svg");
+const ERROR: &str = include_str!("../icons/error.svg");
+const LOUD: &str = include_str!("../icons/loud.svg");
+const PAUSED: &str = include_str!("../icons/paused.svg");
+const HEADSET: &str = include_str!("../icons/headset.svg");
+const CAMERA: &str = include_str!("../icons/camera.svg");
+
+/// The icon names that the tray uses by default, with their fallback icon.
+const ICONS: [(&str, &str); 12] = [
+    ("microphone-sensitivity-high-symbolic", MIC_ON),
+    ("microphone-sensitivity-medium-symbolic", MIC_MEDIUM),
+    ("microphone-sensitivity-low-symbolic", MIC_OFF),
+    ("microphone-sensitivity-muted-symbolic", MIC_MUTED),
+    ("audio-input-microphone-muted-symbolic", MIC_MUTED),
+    ("microphone-disabled-symbolic", MIC_MUTED),
+    ("dialog-warning-symbolic", WARNING),
+    ("dialog-error-symbolic", ERROR),
+    ("audio-volume-overamplified-symbolic", LOUD),
+    ("media-playback-pause-symbolic", PAUSED),
+    ("audio-headset-symbolic", HEADSET),
+    ("camera-web-symbolic", CAMERA),
+];
+
+/// Write the fallback icons to a directory, which the tray can add to the
+/// icon theme search path.
+///
+/// Icons in the search path are only used if the icon theme has no icon of
+/// the same name, so the theme's icons are still preferred where they exist.
+pub fn install() -> Option<PathBuf> {
+    let dir = glib::user_runtime_dir().join("pw-micclick").join("icons");
+    match write(&dir) {
+        Ok(()) => Some(dir),
+        Err(e) => {
+            eprintln!("error: cannot install the fallback icons: {e:#}");
+            None
+        }
+    }
+}
+
+fn write(dir: &Path) -> Result<()> {
+    std::fs::create_dir_all(dir)
+        .with_context(|| format!("cannot create directory {}", dir.display()))?;
+    for (name, svg) in ICONS {
+        let path = dir.join(format!("{name}.svg"));
+        std::fs::write(&path, svg).with_context(|| format!("cannot write {}", path.display()))?;
+    }
+    Ok(())
+}
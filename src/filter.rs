@@ -0,0 +1,171 @@
+//! Simple IIR filters that are applied to captured samples before measuring their level.
+
+use std::f32::consts::{FRAC_1_SQRT_2, PI};
+
+/// Cutoff frequency in Hz of the DC blocking filter.
+const DC_BLOCK_CUTOFF: f32 = 10.;
+
+/// A second-order IIR filter section.
+///
+/// Coefficients are calculated according to Robert Bristow-Johnson's "Audio EQ
+/// Cookbook".
+#[derive(Debug, Copy, Clone)]
+pub struct Biquad {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+    x1: f32,
+    x2: f32,
+    y1: f32,
+    y2: f32,
+}
+
+impl Biquad {
+    /// Create a first-order DC blocking filter, with a cutoff frequency of
+    /// `DC_BLOCK_CUTOFF` Hz.
+    pub fn dc_blocker(rate: u32) -> Self {
+        let r = 1. - 2. * PI * DC_BLOCK_CUTOFF / rate as f32;
+        Self::new(1., -1., 0., 1., -r, 0.)
+    }
+
+    /// Create a high-pass filter with the given cutoff frequency in Hz.
+    pub fn highpass(rate: u32, cutoff: f32) -> Self {
+        let (cos, alpha) = Self::params(rate, cutoff);
+        Self::new(
+            (1. + cos) / 2.,
+            -(1. + cos),
+            (1. + cos) / 2.,
+            1. + alpha,
+            -2. * cos,
+            1. - alpha,
+        )
+    }
+
+    /// Create a low-pass filter with the given cutoff frequency in Hz.
+    pub fn lowpass(rate: u32, cutoff: f32) -> Self {
+        let (cos, alpha) = Self::params(rate, cutoff);
+        Self::new(
+            (1. - cos) / 2.,
+            1. - cos,
+            (1. - cos) / 2.,
+            1. + alpha,
+            -2. * cos,
+            1. - alpha,
+        )
+    }
+
+    /// Create a digital filter from an analog second-order transfer function
+    /// `(b0 s^2 + b1 s + b2) / (a0 s^2 + a1 s + a2)` using the bilinear transform.
+    fn bilinear(rate: u32, b: [f32; 3], a: [f32; 3]) -> Self {
+        let k = 2. * rate as f32;
+        let k2 = k * k;
+        Self::new(
+            b[0] * k2 + b[1] * k + b[2],
+            2. * (b[2] - b[0] * k2),
+            b[0] * k2 - b[1] * k + b[2],
+            a[0] * k2 + a[1] * k + a[2],
+            2. * (a[2] - a[0] * k2),
+            a[0] * k2 - a[1] * k + a[2],
+        )
+    }
+
+    fn params(rate: u32, cutoff: f32) -> (f32, f32) {
+        let w0 = 2. * PI * cutoff / rate as f32;
+        (w0.cos(), w0.sin() / (2. * FRAC_1_SQRT_2))
+    }
+
+    fn new(b0: f32, b1: f32, b2: f32, a0: f32, a1: f32, a2: f32) -> Self {
+        Self {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: a1 / a0,
+            a2: a2 / a0,
+            x1: 0.,
+            x2: 0.,
+            y1: 0.,
+            y2: 0.,
+        }
+    }
+
+    /// Calculate the filter's gain at the given frequency in Hz.
+    fn magnitude(&self, rate: u32, freq: f32) -> f32 {
+        let w = 2. * PI * freq / rate as f32;
+        let (c1, s1) = (w.cos(), w.sin());
+        let (c2, s2) = ((2. * w).cos(), (2. * w).sin());
+        let num_re = self.b0 + self.b1 * c1 + self.b2 * c2;
+        let num_im = -self.b1 * s1 - self.b2 * s2;
+        let den_re = 1. + self.a1 * c1 + self.a2 * c2;
+        let den_im = -self.a1 * s1 - self.a2 * s2;
+        (num_re.hypot(num_im)) / (den_re.hypot(den_im))
+    }
+
+    /// Feed one sample through the filter and return the filtered sample.
+    pub fn process(&mut self, x: f32) -> f32 {
+        let y = self.b0 * x + self.b1 * self.x1 + self.b2 * self.x2
+            - self.a1 * self.y1
+            - self.a2 * self.y2;
+        self.x2 = self.x1;
+        self.x1 = x;
+        self.y2 = self.y1;
+        self.y1 = y;
+        y
+    }
+}
+
+/// Create a chain of filters that implements the A-weighting curve from IEC
+/// 61672-1, normalized to unity gain at 1 kHz.
+pub fn a_weighting(rate: u32) -> [Biquad; 3] {
+    let w1 = 2. * PI * 20.598_997;
+    let w2 = 2. * PI * 107.652_65;
+    let w3 = 2. * PI * 737.862_2;
+    let w4 = 2. * PI * 12194.217;
+
+    let mut chain = [
+        Biquad::bilinear(rate, [1., 0., 0.], [1., 2. * w1, w1 * w1]),
+        Biquad::bilinear(rate, [1., 0., 0.], [1., w2 + w3, w2 * w3]),
+        Biquad::bilinear(rate, [0., 0., w4 * w4], [1., 2. * w4, w4 * w4]),
+    ];
+    let gain: f32 = chain.iter().map(|f| f.magnitude(rate, 1000.)).product();
+    chain[0].b0 /= gain;
+    chain[0].b1 /= gain;
+    chain[0].b2 /= gain;
+    chain
+}
+
+/// Create the two-stage K-weighting filter from ITU-R BS.1770, which is used
+/// to measure loudness in LUFS.
+///
+/// The coefficients are calculated for arbitrary sample rates as done by libebur128.
+pub fn k_weighting(rate: u32) -> [Biquad; 2] {
+    let f0 = 1681.974_5;
+    let gain = 3.999_843_9;
+    let q = 0.707_175_24;
+    let k = (PI * f0 / rate as f32).tan();
+    let vh = 10f32.powf(gain / 20.);
+    let vb = vh.powf(0.499_666_77);
+    let shelf = Biquad::new(
+        vh + vb * k / q + k * k,
+        2. * (k * k - vh),
+        vh - vb * k / q + k * k,
+        1. + k / q + k * k,
+        2. * (k * k - 1.),
+        1. - k / q + k * k,
+    );
+
+    let f0 = 38.135_47;
+    let q = 0.500_327_04;
+    let k = (PI * f0 / rate as f32).tan();
+    let highpass = Biquad::new(
+        1.,
+        -2.,
+        1.,
+        1. + k / q + k * k,
+        2. * (k * k - 1.),
+        1. - k / q + k * k,
+    );
+
+    [shelf, highpass]
+}
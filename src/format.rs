@@ -0,0 +1,112 @@
+//! Sample formats that the capture stream can work with.
+
+use libspa::buffer::Data;
+use libspa_sys::*;
+
+/// The formats that are offered when connecting the capture stream, in order of preference.
+pub const FORMATS: [spa_audio_format; 4] = [
+    SPA_AUDIO_FORMAT_F32,
+    SPA_AUDIO_FORMAT_F32P,
+    SPA_AUDIO_FORMAT_S32,
+    SPA_AUDIO_FORMAT_S16,
+];
+
+/// The type of the individual samples in a captured buffer.
+///
+/// Planar and interleaved formats are not distinguished here; planar samples
+/// are interleaved after reading them.
+#[derive(Debug, Copy, Clone)]
+pub enum SampleFormat {
+    F32,
+    S32,
+    S16,
+}
+
+impl SampleFormat {
+    pub fn from_raw(format: spa_audio_format) -> Option<Self> {
+        match format {
+            SPA_AUDIO_FORMAT_F32 | SPA_AUDIO_FORMAT_F32P => Some(Self::F32),
+            SPA_AUDIO_FORMAT_S32 => Some(Self::S32),
+            SPA_AUDIO_FORMAT_S16 => Some(Self::S16),
+            _ => None,
+        }
+    }
+
+    /// The size of one sample in bytes.
+    pub fn sample_size(self) -> usize {
+        match self {
+            Self::F32 | Self::S32 => 4,
+            Self::S16 => 2,
+        }
+    }
+
+    /// Check whether the given format stores each channel in a separate data buffer.
+    pub fn is_planar(format: spa_audio_format) -> bool {
+        format == SPA_AUDIO_FORMAT_F32P
+    }
+
+    /// Convert the samples in a data buffer to f32 and append them to `out`.
+    ///
+    /// A chunk that claims to be larger than its buffer is cut to the buffer,
+    /// and a trailing partial sample is ignored. The samples are read byte by
+    /// byte, so they do not need to be aligned.
+    pub fn read_samples(self, data: &mut Data, out: &mut Vec<f32>) {
+        let offset = data.chunk().offset() as usize;
+        let size = data.chunk().size() as usize;
+        let Some(bytes) = data.data() else {
+            return;
+        };
+        let start = offset.min(bytes.len());
+        let end = start.saturating_add(size).min(bytes.len());
+        let bytes = &bytes[start..end];
+        match self {
+            Self::F32 => out.extend(
+                bytes
+                    .chunks_exact(4)
+                    .map(|b| f32::from_ne_bytes(b.try_into().unwrap())),
+            ),
+            Self::S32 => out.extend(
+                bytes
+                    .chunks_exact(4)
+                    .map(|b| i32::from_ne_bytes(b.try_into().unwrap()) as f32 / 2147483648.),
+            ),
+            Self::S16 => out.extend(
+                bytes
+                    .chunks_exact(2)
+                    .map(|b| i16::from_ne_bytes(b.try_into().unwrap()) as f32 / 32768.),
+            ),
+        }
+    }
+}
+
+/// Interleave the samples of consecutive equally sized planes, and append them to `out`.
+pub fn interleave(planes: &[f32], n_channels: usize, out: &mut Vec<f32>) {
+    let n_frames = planes.len() / n_channels;
+    out.reserve(n_frames * n_channels);
+    for frame in 0..n_frames {
+        out.extend(planes[frame..].iter().step_by(n_frames).take(n_channels));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offered_formats_are_supported() {
+        for format in FORMATS {
+            assert!(SampleFormat::from_raw(format).is_some());
+        }
+        assert!(SampleFormat::from_raw(SPA_AUDIO_FORMAT_U8).is_none());
+        assert!(SampleFormat::is_planar(SPA_AUDIO_FORMAT_F32P));
+        assert!(!SampleFormat::is_planar(SPA_AUDIO_FORMAT_F32));
+    }
+
+    #[test]
+    fn interleave_planes() {
+        let planes = [1., 2., 3., 10., 20., 30.];
+        let mut out = vec![0.];
+        interleave(&planes, 2, &mut out);
+        assert_eq!(out, [0., 1., 10., 2., 20., 3., 30.]);
+    }
+}
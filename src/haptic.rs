@@ -0,0 +1,127 @@
+//! Rumble feedback through force-feedback input devices, like gamepads.
+
+use anyhow::{Context, Result};
+use std::ffi::{c_int, c_ulong};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::os::fd::AsRawFd;
+use std::time::Duration;
+
+const FF_RUMBLE: u16 = 0x50;
+const EV_FF: u16 = 0x15;
+
+#[repr(C)]
+#[derive(Default)]
+struct FfTrigger {
+    button: u16,
+    interval: u16,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct FfReplay {
+    length: u16,
+    delay: u16,
+}
+
+/// `struct ff_effect`, where the union is only ever used as `struct ff_rumble_effect`.
+#[repr(C)]
+#[derive(Default)]
+struct FfEffect {
+    type_: u16,
+    id: i16,
+    direction: u16,
+    trigger: FfTrigger,
+    replay: FfReplay,
+    /// The union is aligned for the pointer in `struct ff_periodic_effect`.
+    _align: u16,
+    /// The strong and weak magnitudes, padded to the size of the largest union member.
+    rumble: [u16; 2],
+    _union_padding: [u32; 7],
+}
+
+/// `struct input_event`, on platforms with a 64 bit `time_t`.
+#[repr(C)]
+struct InputEvent {
+    time: [i64; 2],
+    type_: u16,
+    code: u16,
+    value: i32,
+}
+
+/// `EVIOCSFF`, which is `_IOW('E', 0x80, struct ff_effect)`.
+const EVIOCSFF: c_ulong = (1 << 30)
+    | ((std::mem::size_of::<FfEffect>() as c_ulong) << 16)
+    | ((b'E' as c_ulong) << 8)
+    | 0x80;
+
+extern "C" {
+    fn ioctl(fd: c_int, request: c_ulong, ...) -> c_int;
+}
+
+/// A force-feedback device with one rumble effect for the microphone turning
+/// on, and a shorter and weaker one for it turning off.
+pub struct Haptic {
+    device: File,
+    on_effect: i16,
+    off_effect: i16,
+}
+
+impl Haptic {
+    /// Open an evdev device like /dev/input/event5, and upload the effects to it.
+    pub fn open(path: &str) -> Result<Self> {
+        let device = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)
+            .with_context(|| format!("cannot open {path}"))?;
+        let on_effect = upload_rumble(&device, Duration::from_millis(200), 0xc000, 0x8000)
+            .with_context(|| format!("{path} does not support rumble effects"))?;
+        let off_effect = upload_rumble(&device, Duration::from_millis(80), 0x4000, 0x8000)
+            .with_context(|| format!("{path} does not support rumble effects"))?;
+        Ok(Self {
+            device,
+            on_effect,
+            off_effect,
+        })
+    }
+
+    /// Pulse once, strongly if the microphone turned on, or weakly if it turned off.
+    pub fn pulse(&mut self, on: bool) {
+        let effect = if on { self.on_effect } else { self.off_effect };
+        let event = InputEvent {
+            time: [0; 2],
+            type_: EV_FF,
+            code: effect as u16,
+            value: 1,
+        };
+        let bytes = unsafe {
+            std::slice::from_raw_parts(
+                &event as *const InputEvent as *const u8,
+                std::mem::size_of::<InputEvent>(),
+            )
+        };
+        if let Err(e) = self.device.write_all(bytes) {
+            eprintln!("error: cannot play rumble effect: {e}");
+        }
+    }
+}
+
+/// Upload a rumble effect to the device, and return its id.
+fn upload_rumble(device: &File, length: Duration, strong: u16, weak: u16) -> Result<i16> {
+    let mut effect = FfEffect {
+        type_: FF_RUMBLE,
+        // Let the kernel allocate an id.
+        id: -1,
+        replay: FfReplay {
+            length: length.as_millis() as u16,
+            delay: 0,
+        },
+        rumble: [strong, weak],
+        ..Default::default()
+    };
+    if unsafe { ioctl(device.as_raw_fd(), EVIOCSFF, &mut effect as *mut FfEffect) } < 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(effect.id)
+}
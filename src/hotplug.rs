@@ -0,0 +1,129 @@
+//! Watching the registry for devices that appear or disappear.
+
+use crate::pattern;
+use anyhow::Result;
+use pipewire::core::{self, Core, PW_ID_CORE};
+use pipewire::keys;
+use pipewire::registry::{self, Registry};
+use pipewire::types::ObjectType;
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::rc::Rc;
+
+/// Keeps the registry and core listeners alive.
+pub struct DeviceWatcher {
+    _registry: Registry,
+    _listener: registry::Listener,
+    _core_listener: core::Listener,
+    state: Rc<RefCell<State>>,
+}
+
+impl DeviceWatcher {
+    /// Get the id of a suitable node, if there is one.
+    pub fn first_device(&self) -> Option<u32> {
+        self.state.borrow().devices.first().copied()
+    }
+}
+
+#[derive(Default)]
+struct State {
+    /// The ids of all suitable nodes.
+    devices: BTreeSet<u32>,
+    /// Whether the initial set of nodes is known.
+    initialized: bool,
+}
+
+/// Call `on_change` with whether a suitable device is available, whenever that changes.
+///
+/// A node is suitable if it has the given media class, its name does not
+/// match any of the `exclude` patterns and, if a target is given, it matches
+/// the target by node name or object serial.
+pub fn watch(
+    core: &Core,
+    media_class: &'static str,
+    target: Option<String>,
+    exclude: Vec<String>,
+    on_change: impl Fn(bool) + 'static,
+) -> Result<DeviceWatcher> {
+    let registry = core.get_registry()?;
+    let state: Rc<RefCell<State>> = Rc::default();
+    let on_change = Rc::new(on_change);
+
+    let listener = registry
+        .add_listener_local()
+        .global({
+            let state = state.clone();
+            let on_change = on_change.clone();
+            move |global| {
+                if global.type_ != ObjectType::Node {
+                    return;
+                }
+                let Some(props) = global.props else {
+                    return;
+                };
+                if props.get(*keys::MEDIA_CLASS) != Some(media_class) {
+                    return;
+                }
+                let name = props.get(*keys::NODE_NAME);
+                if exclude
+                    .iter()
+                    .any(|p| pattern::matches(p, name.unwrap_or_default()))
+                {
+                    return;
+                }
+                if let Some(ref target) = target {
+                    let serial = props.get(*keys::OBJECT_SERIAL);
+                    if name != Some(target.as_str()) && serial != Some(target.as_str()) {
+                        return;
+                    }
+                }
+                let mut state = state.borrow_mut();
+                if state.devices.insert(global.id) && state.devices.len() == 1 && state.initialized
+                {
+                    drop(state);
+                    on_change(true);
+                }
+            }
+        })
+        .global_remove({
+            let state = state.clone();
+            let on_change = on_change.clone();
+            move |id| {
+                let mut state = state.borrow_mut();
+                if state.devices.remove(&id) && state.devices.is_empty() && state.initialized {
+                    drop(state);
+                    on_change(false);
+                }
+            }
+        })
+        .register();
+
+    let pending = core.sync(0)?;
+    let core_listener = core
+        .add_listener_local()
+        .done({
+            let state = state.clone();
+            move |id, seq| {
+                if id != PW_ID_CORE || seq != pending {
+                    return;
+                }
+                let mut state = state.borrow_mut();
+                if state.initialized {
+                    return;
+                }
+                state.initialized = true;
+                if state.devices.is_empty() {
+                    drop(state);
+                    on_change(false);
+                }
+            }
+        })
+        .register();
+
+    Ok(DeviceWatcher {
+        _registry: registry,
+        _listener: listener,
+        _core_listener: core_listener,
+        state,
+    })
+}
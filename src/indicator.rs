@@ -0,0 +1,307 @@
+//! The tray icon, implemented with libappindicator.
+
+use crate::app_indicator::AppIndicator;
+use crate::config::Settings;
+use crate::overlay::Overlay;
+use crate::tray::{self, TrayActions, TrayState, Update};
+use crate::{
+    activity_log, config, fallback_icons, level_history, level_meter, settings, wizard, Event,
+    Meter, THRESHOLD_STEP,
+};
+use gtk::prelude::*;
+use libappindicator::AppIndicatorStatus;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+pub fn thread_main(
+    eventreceiver: async_channel::Receiver<Event>,
+    mut state: TrayState,
+    actions: TrayActions,
+) {
+    gtk::init().expect("gtk::init() failed");
+    let icon_theme_path = fallback_icons::install();
+    if let (Some(path), Some(theme)) = (&icon_theme_path, gtk::IconTheme::default()) {
+        theme.append_search_path(path);
+    }
+
+    let pause_item = gtk::MenuItem::with_label(state.pause_label());
+    pause_item.connect_activate({
+        let actions = actions.clone();
+        move |_| actions.toggle_pause()
+    });
+    // The thresholds that were adjusted in the tray, to show them briefly.
+    let (notice_sender, notices) = async_channel::unbounded();
+    let threshold_item = gtk::MenuItem::with_label(&actions.threshold_label());
+    let threshold_menu = gtk::Menu::new();
+    for (label, step) in tray::threshold_steps() {
+        let item = gtk::MenuItem::with_label(&label);
+        let actions = actions.clone();
+        let notice_sender = notice_sender.clone();
+        item.connect_activate(move |_| actions.adjust_threshold(step, &notice_sender));
+        threshold_menu.append(&item);
+    }
+    threshold_item.set_submenu(Some(&threshold_menu));
+    let source_item = gtk::MenuItem::with_label("Source");
+    let source_menu = gtk::Menu::new();
+    fill_sources(&source_menu, &actions);
+    source_item.set_submenu(Some(&source_menu));
+    source_item.set_sensitive(state.can_select_source());
+    let quit_item = gtk::MenuItem::with_label("Quit");
+    quit_item.connect_activate({
+        let actions = actions.clone();
+        move |_| actions.quit()
+    });
+    let silence_item = gtk::MenuItem::with_label(state.silence_label());
+    silence_item.connect_activate({
+        let actions = actions.clone();
+        move |_| actions.toggle_silenced()
+    });
+    let test_items = [("Test on sound", true), ("Test off sound", false)].map(|(label, on)| {
+        let item = gtk::MenuItem::with_label(label);
+        let actions = actions.clone();
+        item.connect_activate(move |_| actions.test_sound(on));
+        item
+    });
+    let args = state.args();
+    let settings = Rc::new(RefCell::new(Settings {
+        threshold: args.threshold_on.unwrap_or(args.threshold),
+        hold_time: args.hold_time,
+        on_sound: args.on_sound.clone(),
+        off_sound: args.off_sound.clone(),
+        target: args.targets.first().cloned(),
+    }));
+    let settings_item = gtk::MenuItem::with_label("Settings…");
+    settings_item.connect_activate({
+        let settings = settings.clone();
+        let config_path = args.config.clone();
+        let actions = actions.clone();
+        let window: Rc<glib::WeakRef<gtk::Window>> = Rc::default();
+        move |_| {
+            present(&window, || {
+                settings::window(settings.clone(), config_path.clone(), actions.clone())
+            })
+        }
+    });
+    let open_config_item = gtk::MenuItem::with_label("Open configuration");
+    open_config_item.connect_activate({
+        let actions = actions.clone();
+        move |_| actions.open_config()
+    });
+    let reload_config_item = gtk::MenuItem::with_label("Reload configuration");
+    reload_config_item.connect_activate({
+        let actions = actions.clone();
+        move |_| actions.reload_config()
+    });
+    // The level that the meter window shows, if it is open.
+    let meter: Rc<Cell<Option<Meter>>> = Rc::default();
+    let meter_window: Rc<glib::WeakRef<gtk::Window>> = Rc::default();
+    let meter_item = gtk::MenuItem::with_label("Level meter");
+    meter_item.connect_activate({
+        let meter = meter.clone();
+        let meter_window = meter_window.clone();
+        let actions = actions.clone();
+        let args = args.clone();
+        move |_| {
+            present(&meter_window, || {
+                level_meter::window(meter.clone(), actions.clone(), args.clone())
+            })
+        }
+    });
+    let history: level_history::History = Rc::default();
+    let history_window: Rc<glib::WeakRef<gtk::Window>> = Rc::default();
+    let history_item = gtk::MenuItem::with_label("Level history");
+    history_item.connect_activate({
+        let history = history.clone();
+        let history_window = history_window.clone();
+        let actions = actions.clone();
+        let args = args.clone();
+        move |_| {
+            present(&history_window, || {
+                level_history::window(history.clone(), actions.clone(), args.clone())
+            })
+        }
+    });
+    let activity_store = activity_log::store();
+    let activity_item = gtk::MenuItem::with_label("Activity log");
+    activity_item.connect_activate({
+        let activity_store = activity_store.clone();
+        let window: Rc<glib::WeakRef<gtk::Window>> = Rc::default();
+        move |_| present(&window, || activity_log::window(&activity_store))
+    });
+    let mut activity_changes = state.activity_changes();
+    let mut overlays = vec![];
+    #[cfg(feature = "layer-shell")]
+    overlays.extend(state.args().overlay.and_then(Overlay::layer));
+    if state.args().window_indicator {
+        overlays.push(Overlay::window());
+    }
+    for overlay in &overlays {
+        overlay.draw(&state);
+    }
+
+    let indicator = AppIndicator::new("pw-micclick");
+    indicator.set_status(AppIndicatorStatus::Passive);
+    indicator.set_icon_full(state.icon().0, "icon");
+    indicator.set_attention_icon_full("dialog-warning-symbolic", "talking while muted");
+    if let Some(path) = icon_theme_path.as_deref().and_then(|path| path.to_str()) {
+        indicator.set_icon_theme_path(path);
+    }
+    let menu = gtk::Menu::new();
+    menu.append(&pause_item);
+    menu.append(&silence_item);
+    menu.append(&threshold_item);
+    menu.append(&source_item);
+    for item in &test_items {
+        menu.append(item);
+    }
+    menu.append(&meter_item);
+    menu.append(&history_item);
+    menu.append(&activity_item);
+    menu.append(&settings_item);
+    menu.append(&open_config_item);
+    menu.append(&reload_config_item);
+    menu.append(&gtk::SeparatorMenuItem::new());
+    menu.append(&quit_item);
+    indicator.set_menu(&menu);
+    indicator.set_secondary_activate_target(&pause_item);
+    indicator.connect_scroll({
+        let actions = actions.clone();
+        move |direction| match direction {
+            gtk::gdk::ScrollDirection::Up => {
+                actions.adjust_threshold(Some(THRESHOLD_STEP), &notice_sender)
+            }
+            gtk::gdk::ScrollDirection::Down => {
+                actions.adjust_threshold(Some(-THRESHOLD_STEP), &notice_sender)
+            }
+            _ => {}
+        }
+    });
+    menu.show_all();
+
+    // A separate tray icon, which is only shown while a camera is in use.
+    let camera_indicator = AppIndicator::new("pw-micclick-camera");
+    camera_indicator.set_status(AppIndicatorStatus::Passive);
+    camera_indicator.set_icon_full("camera-web-symbolic", "camera");
+    if let Some(path) = icon_theme_path.as_deref().and_then(|path| path.to_str()) {
+        camera_indicator.set_icon_theme_path(path);
+    }
+    let camera_menu = gtk::Menu::new();
+    camera_indicator.set_menu(&camera_menu);
+    camera_menu.show_all();
+
+    // Help with the initial settings on the first start.
+    let config_path = args.config.clone().unwrap_or_else(config::default_path);
+    let wizard = (!config_path.exists())
+        .then(|| wizard::show(settings, args.config.clone(), actions.clone(), args.clone()));
+
+    glib::MainContext::default().spawn_local(async move {
+        while let Some(update) = state.next_update(&eventreceiver, &notices).await {
+            if update == Update::Cameras {
+                show_cameras(&camera_indicator, &state);
+            }
+            if update == Update::Sources {
+                fill_sources(&source_menu, &actions);
+            }
+            if update == Update::Icon || (update == Update::Level && state.args().tray_level) {
+                show_state(&indicator, &state);
+                threshold_item.set_label(&actions.threshold_label());
+                pause_item.set_label(state.pause_label());
+                silence_item.set_label(state.silence_label());
+                for overlay in &overlays {
+                    overlay.draw(&state);
+                }
+            }
+            if state.activity_changes() != activity_changes {
+                activity_changes = state.activity_changes();
+                activity_log::fill(&activity_store, &state);
+            }
+            if let (Update::Level, Some(wizard)) = (update, &wizard) {
+                wizard.level(state.meter());
+            }
+            if update == Update::Icon || update == Update::Level {
+                meter.set(state.meter());
+                if let Some(window) = meter_window.upgrade() {
+                    window.queue_draw();
+                }
+            }
+            if update == Update::Level {
+                level_history::record(&history, state.meter());
+                if let Some(window) = history_window.upgrade() {
+                    window.queue_draw();
+                }
+            }
+        }
+        gtk::main_quit();
+    });
+    gtk::main();
+}
+
+/// Show the state of the shown source, and whether the icon is visible at all.
+fn show_state(indicator: &AppIndicator, state: &TrayState) {
+    let (icon, description) = state.icon();
+    indicator.set_icon_full(icon, description);
+    indicator.set_status(if !state.visible() {
+        AppIndicatorStatus::Passive
+    } else if state.needs_attention() {
+        AppIndicatorStatus::Attention
+    } else {
+        AppIndicatorStatus::Active
+    });
+    indicator.set_label(&state.label().unwrap_or_default(), "00:00 live  -100.0 dB");
+    indicator.set_title(&state.title());
+}
+
+/// Show the camera tray icon while any application uses a camera.
+fn show_cameras(indicator: &AppIndicator, state: &TrayState) {
+    match state.camera_title() {
+        Some(title) => {
+            indicator.set_title(&title);
+            indicator.set_status(AppIndicatorStatus::Active);
+        }
+        None => indicator.set_status(AppIndicatorStatus::Passive),
+    }
+}
+
+/// Fill the source submenu with the sources that can be monitored, with the
+/// selected one checked.
+fn fill_sources(menu: &gtk::Menu, actions: &TrayActions) {
+    for child in menu.children() {
+        menu.remove(&child);
+    }
+    let selected = actions.selected_source();
+    let nodes = actions
+        .sources()
+        .into_iter()
+        .map(|node| (Some(node.id), node.description));
+    let mut group: Option<gtk::RadioMenuItem> = None;
+    for (id, label) in [(None, tray::AUTOMATIC_SOURCE.to_owned())]
+        .into_iter()
+        .chain(nodes)
+    {
+        let item = gtk::RadioMenuItem::with_label(&label);
+        match &group {
+            Some(group) => item.join_group(Some(group)),
+            None => group = Some(item.clone()),
+        }
+        item.set_active(id == selected);
+        let actions = actions.clone();
+        item.connect_toggled(move |item| {
+            if item.is_active() {
+                actions.select_source(id);
+            }
+        });
+        menu.append(&item);
+    }
+    menu.show_all();
+}
+
+/// Bring a window to the front, or create and show it if it is not open.
+fn present(window: &glib::WeakRef<gtk::Window>, create: impl FnOnce() -> gtk::Window) {
+    if let Some(window) = window.upgrade() {
+        window.present();
+        return;
+    }
+    let new = create();
+    new.show_all();
+    window.set(Some(&new));
+}
@@ -0,0 +1,96 @@
+//! The level history window, which draws the input level of the shown source
+//! over the last minute as a sparkline, together with the thresholds, so that
+//! single spikes can be told apart from a rising noise floor.
+
+use crate::level_meter::METER_FLOOR;
+use crate::tray::TrayActions;
+use crate::{Args, Meter};
+use gtk::prelude::*;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// How far back the history goes.
+const HISTORY_TIME: Duration = Duration::from_secs(60);
+
+/// The linear levels of the shown source before gating over the last minute,
+/// or None while there was no input.
+pub type History = Rc<RefCell<VecDeque<(Instant, Option<f32>)>>>;
+
+/// Add the current level to the history, and forget the levels that are too
+/// old to be drawn.
+pub fn record(history: &History, meter: Option<Meter>) {
+    let now = Instant::now();
+    let mut history = history.borrow_mut();
+    // The ungated level, so that the noise floor shows even while speech
+    // detection, classification or gating zero the level.
+    history.push_back((now, meter.map(|meter| meter.ungated)));
+    while let Some(&(time, _)) = history.front() {
+        if now.duration_since(time) <= HISTORY_TIME {
+            break;
+        }
+        history.pop_front();
+    }
+}
+
+/// Create the level history window, which draws `history` whenever it is
+/// redrawn.
+pub fn window(history: History, actions: TrayActions, args: Args) -> gtk::Window {
+    let window = gtk::Window::new(gtk::WindowType::Toplevel);
+    window.set_title("pw-micclick level history");
+    window.set_default_size(480, 120);
+
+    let area = gtk::DrawingArea::new();
+    area.connect_draw(move |area, cr| {
+        let width = f64::from(area.allocated_width());
+        let height = f64::from(area.allocated_height());
+        let now = Instant::now();
+        // The position of a point in time, and of a linear level.
+        let x = |time: Instant| {
+            let age = now.duration_since(time).min(HISTORY_TIME);
+            (1. - age.as_secs_f64() / HISTORY_TIME.as_secs_f64()) * width
+        };
+        let y = |level: f32| {
+            let db = args.db(level).clamp(METER_FLOOR, 0.);
+            f64::from(db / METER_FLOOR) * height
+        };
+        let (threshold_on, threshold_off) = actions.thresholds();
+
+        cr.set_source_rgb(0.15, 0.15, 0.15);
+        let _ = cr.paint();
+        // The on threshold is drawn last, so that it covers an equal off threshold.
+        for (level, (red, green, blue)) in [
+            (threshold_off, (1., 0.6, 0.)),
+            (threshold_on, (1., 0.2, 0.2)),
+        ] {
+            cr.set_source_rgb(red, green, blue);
+            cr.rectangle(0., y(level) - 0.5, width, 1.);
+            let _ = cr.fill();
+        }
+
+        cr.set_source_rgb(0.2, 0.7, 0.3);
+        cr.set_line_width(1.5);
+        let mut drawing = false;
+        for &(time, level) in history.borrow().iter() {
+            match level {
+                Some(level) if drawing => cr.line_to(x(time), y(level)),
+                Some(level) => {
+                    cr.move_to(x(time), y(level));
+                    drawing = true;
+                }
+                // Leave a gap while there was no input.
+                None => drawing = false,
+            }
+        }
+        let _ = cr.stroke();
+
+        cr.set_source_rgb(1., 1., 1.);
+        cr.set_font_size(14.);
+        cr.move_to(8., height - 8.);
+        let _ = cr.show_text(&format!("Last {} s", HISTORY_TIME.as_secs()));
+        glib::Propagation::Stop
+    });
+    window.add(&area);
+    window
+}
@@ -0,0 +1,69 @@
+//! The level meter window, which shows the input level of the source that the
+//! tray icon shows, together with the thresholds.
+
+use crate::tray::TrayActions;
+use crate::{Args, Meter};
+use gtk::prelude::*;
+use std::cell::Cell;
+use std::rc::Rc;
+
+/// The lowest level in dB that the meter shows.
+pub const METER_FLOOR: f32 = -100.;
+
+/// Create the level meter window, which draws `meter` whenever it is redrawn.
+pub fn window(meter: Rc<Cell<Option<Meter>>>, actions: TrayActions, args: Args) -> gtk::Window {
+    let window = gtk::Window::new(gtk::WindowType::Toplevel);
+    window.set_title("pw-micclick level");
+    window.set_default_size(360, 64);
+
+    let area = gtk::DrawingArea::new();
+    area.connect_draw(move |area, cr| {
+        let width = f64::from(area.allocated_width());
+        let height = f64::from(area.allocated_height());
+        // The position of a linear level on the meter.
+        let x = |level: f32| {
+            let db = args.db(level).clamp(METER_FLOOR, 0.);
+            f64::from((db - METER_FLOOR) / -METER_FLOOR) * width
+        };
+        let (threshold_on, threshold_off) = actions.thresholds();
+
+        cr.set_source_rgb(0.15, 0.15, 0.15);
+        let _ = cr.paint();
+        let text = match meter.get() {
+            Some(meter) => {
+                if meter.level > threshold_on {
+                    cr.set_source_rgb(0.2, 0.7, 0.3);
+                } else {
+                    cr.set_source_rgb(0.4, 0.45, 0.5);
+                }
+                cr.rectangle(0., 0., x(meter.level), height);
+                let _ = cr.fill();
+                cr.set_source_rgb(0.9, 0.9, 0.9);
+                cr.rectangle(x(meter.peak) - 1., 0., 2., height);
+                let _ = cr.fill();
+                format!(
+                    "{:.1} dB (peak {:.1} dB)",
+                    args.db(meter.level),
+                    args.db(meter.peak)
+                )
+            }
+            None => "No input".to_owned(),
+        };
+        // The on threshold is drawn last, so that it covers an equal off threshold.
+        for (level, (red, green, blue)) in [
+            (threshold_off, (1., 0.6, 0.)),
+            (threshold_on, (1., 0.2, 0.2)),
+        ] {
+            cr.set_source_rgb(red, green, blue);
+            cr.rectangle(x(level) - 1., 0., 2., height);
+            let _ = cr.fill();
+        }
+        cr.set_source_rgb(1., 1., 1.);
+        cr.set_font_size(14.);
+        cr.move_to(8., height - 8.);
+        let _ = cr.show_text(&text);
+        glib::Propagation::Stop
+    });
+    window.add(&area);
+    window
+}
@@ -0,0 +1,251 @@
+//! Tracking the node that the capture stream is linked to, and its mute state.
+
+use anyhow::Result;
+use libspa::param::ParamType;
+use libspa::pod::deserialize::PodDeserializer;
+use libspa::pod::{Pod, Value};
+use libspa_sys::{SPA_PROP_mute, SPA_PROP_softMute};
+use pipewire::core::Core;
+use pipewire::keys;
+use pipewire::node::{Node, NodeListener};
+use pipewire::properties::Properties;
+use pipewire::registry::{self, GlobalObject, Registry};
+use pipewire::types::ObjectType;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Keeps the registry and node listeners alive.
+pub struct LinkWatcher {
+    _registry: Rc<Registry>,
+    _listener: registry::Listener,
+    _state: Rc<RefCell<State>>,
+}
+
+#[derive(Default)]
+struct State {
+    /// All nodes, so that the linked one can be bound once the link appears.
+    nodes: HashMap<u32, GlobalObject<Properties>>,
+    /// The currently linked node.
+    bound: Option<(u32, Node, NodeListener)>,
+}
+
+/// Call `on_linked` with the node name whenever the capture stream is linked
+/// to a different node, and `on_mute_change` with whether that node is muted,
+/// whenever that changes.
+///
+/// `stream_node` returns the node id of the capture stream.
+pub fn watch(
+    core: &Core,
+    stream_node: impl Fn() -> u32 + 'static,
+    on_linked: impl Fn(&str) + 'static,
+    on_mute_change: impl Fn(bool) + 'static,
+) -> Result<LinkWatcher> {
+    let registry = Rc::new(core.get_registry()?);
+    let state: Rc<RefCell<State>> = Rc::default();
+    let on_change: Rc<dyn Fn(bool)> = Rc::new(on_mute_change);
+    let muted: Rc<Cell<Option<bool>>> = Rc::default();
+
+    let listener = registry
+        .add_listener_local()
+        .global({
+            let registry = Rc::downgrade(&registry);
+            let state = state.clone();
+            let muted = muted.clone();
+            let on_change = on_change.clone();
+            move |global| {
+                let mut state = state.borrow_mut();
+                match global.type_ {
+                    ObjectType::Node => {
+                        state.nodes.insert(global.id, global.to_owned());
+                        return;
+                    }
+                    ObjectType::Link => {}
+                    _ => return,
+                }
+                let Some(props) = global.props else {
+                    return;
+                };
+                let node_id = |key: &str| props.get(key).and_then(|id| id.parse::<u32>().ok());
+                if node_id(*keys::LINK_INPUT_NODE) != Some(stream_node()) {
+                    return;
+                }
+                let Some(source) = node_id(*keys::LINK_OUTPUT_NODE) else {
+                    return;
+                };
+                if state.bound.as_ref().is_some_and(|(id, _, _)| *id == source) {
+                    return;
+                }
+                let Some(registry) = registry.upgrade() else {
+                    return;
+                };
+                let Some(global) = state.nodes.get(&source) else {
+                    return;
+                };
+                let node: Node = match registry.bind(global) {
+                    Ok(node) => node,
+                    Err(e) => {
+                        eprintln!("error: cannot bind linked node: {e}");
+                        return;
+                    }
+                };
+                let listener = listen_mute(&node, muted.clone(), on_change.clone());
+                let name = global
+                    .props
+                    .as_ref()
+                    .and_then(|props| props.get(*keys::NODE_NAME))
+                    .unwrap_or_default()
+                    .to_owned();
+                state.bound = Some((source, node, listener));
+                drop(state);
+                on_linked(&name);
+            }
+        })
+        .global_remove({
+            let state = state.clone();
+            let muted = muted.clone();
+            move |id| {
+                let mut state = state.borrow_mut();
+                state.nodes.remove(&id);
+                if state
+                    .bound
+                    .as_ref()
+                    .is_some_and(|(bound, _, _)| *bound == id)
+                {
+                    state.bound = None;
+                    drop(state);
+                    // The next node reports its own mute state once it is
+                    // bound, until then none is muted.
+                    if muted.replace(None) == Some(true) {
+                        on_change(false);
+                    }
+                }
+            }
+        })
+        .register();
+
+    Ok(LinkWatcher {
+        _registry: registry,
+        _listener: listener,
+        _state: state,
+    })
+}
+
+/// Keeps the registry and node listeners of [`watch_mute`] alive.
+pub struct MuteWatcher {
+    _registry: Rc<Registry>,
+    _listener: registry::Listener,
+    _bound: Rc<RefCell<Option<(u32, Node, NodeListener)>>>,
+}
+
+/// Call `on_mute_change` with whether the node with the given name is muted,
+/// whenever that changes, regardless of which node the capture stream is
+/// linked to.
+pub fn watch_mute(
+    core: &Core,
+    name: &str,
+    on_mute_change: impl Fn(bool) + 'static,
+) -> Result<MuteWatcher> {
+    let registry = Rc::new(core.get_registry()?);
+    let bound: Rc<RefCell<Option<(u32, Node, NodeListener)>>> = Rc::default();
+    let on_change: Rc<dyn Fn(bool)> = Rc::new(on_mute_change);
+    let muted: Rc<Cell<Option<bool>>> = Rc::default();
+
+    let listener = registry
+        .add_listener_local()
+        .global({
+            let registry = Rc::downgrade(&registry);
+            let bound = bound.clone();
+            let muted = muted.clone();
+            let on_change = on_change.clone();
+            let name = name.to_owned();
+            move |global| {
+                if global.type_ != ObjectType::Node {
+                    return;
+                }
+                let node_name = global
+                    .props
+                    .as_ref()
+                    .and_then(|props| props.get(*keys::NODE_NAME));
+                if node_name != Some(name.as_str()) {
+                    return;
+                }
+                let Some(registry) = registry.upgrade() else {
+                    return;
+                };
+                let node: Node = match registry.bind(global) {
+                    Ok(node) => node,
+                    Err(e) => {
+                        eprintln!("error: cannot bind node {name}: {e}");
+                        return;
+                    }
+                };
+                let listener = listen_mute(&node, muted.clone(), on_change.clone());
+                *bound.borrow_mut() = Some((global.id, node, listener));
+            }
+        })
+        .global_remove({
+            let bound = bound.clone();
+            move |id| {
+                let mut bound = bound.borrow_mut();
+                if bound.as_ref().is_some_and(|(bound, _, _)| *bound == id) {
+                    *bound = None;
+                    // A node without mute state cannot be muted.
+                    if muted.replace(None) == Some(true) {
+                        on_change(false);
+                    }
+                }
+            }
+        })
+        .register();
+
+    Ok(MuteWatcher {
+        _registry: registry,
+        _listener: listener,
+        _bound: bound,
+    })
+}
+
+/// Call `on_change` whenever the mute state of `node`, which is kept in
+/// `muted`, changes.
+fn listen_mute(
+    node: &Node,
+    muted: Rc<Cell<Option<bool>>>,
+    on_change: Rc<dyn Fn(bool)>,
+) -> NodeListener {
+    let listener = node
+        .add_listener_local()
+        .param(move |_, id, _, _, param| {
+            if id != ParamType::Props {
+                return;
+            }
+            let Some(is_muted) = param.and_then(parse_mute) else {
+                return;
+            };
+            if muted.replace(Some(is_muted)) != Some(is_muted) {
+                on_change(is_muted);
+            }
+        })
+        .register();
+    node.subscribe_params(&[ParamType::Props]);
+    listener
+}
+
+/// Check whether a Props param says that the node is muted, either in hardware or in software.
+///
+/// Returns None if the param does not contain any mute state.
+fn parse_mute(param: &Pod) -> Option<bool> {
+    let Ok((_, Value::Object(object))) = PodDeserializer::deserialize_any_from(param.as_bytes())
+    else {
+        return None;
+    };
+    let mut muted = None;
+    for property in object.properties {
+        if property.key == SPA_PROP_mute || property.key == SPA_PROP_softMute {
+            if let Value::Bool(value) = property.value {
+                muted = Some(muted.unwrap_or(false) || value);
+            }
+        }
+    }
+    muted
+}
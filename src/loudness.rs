@@ -0,0 +1,55 @@
+//! Measurement of the EBU R128 momentary loudness.
+
+use crate::filter::{self, Biquad};
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// The length of the sliding window used for the momentary loudness.
+const MOMENTARY_WINDOW: Duration = Duration::from_millis(400);
+
+#[derive(Debug)]
+pub struct MomentaryLoudness {
+    /// The K-weighting filters for each channel.
+    filters: Vec<[Biquad; 2]>,
+    n_channels: u32,
+    powers: VecDeque<f32>,
+    n_windows: usize,
+}
+
+impl MomentaryLoudness {
+    /// Create a new loudness meter, which will be fed with analysis windows of the given length.
+    pub fn new(rate: u32, n_channels: u32, window: Duration) -> Self {
+        let n_windows = (MOMENTARY_WINDOW.as_secs_f32() / window.as_secs_f32()).ceil() as usize;
+        Self {
+            filters: vec![filter::k_weighting(rate); n_channels.max(1) as usize],
+            n_channels,
+            powers: VecDeque::with_capacity(n_windows),
+            n_windows: n_windows.max(1),
+        }
+    }
+
+    /// Process one analysis window of interleaved samples.
+    ///
+    /// Returns the momentary loudness, converted to an amplitude so that it can
+    /// be compared against thresholds; i.e. `20 * log10(result)` is the loudness in LUFS.
+    pub fn process(&mut self, samples: &mut [f32]) -> f32 {
+        for frame in samples.chunks_exact_mut(self.filters.len()) {
+            for (sample, filters) in frame.iter_mut().zip(self.filters.iter_mut()) {
+                for filter in filters.iter_mut() {
+                    *sample = filter.process(*sample);
+                }
+            }
+        }
+        let sum: f32 = samples.iter().map(|s| s * s).sum();
+        let power = sum / samples.len() as f32 * self.n_channels as f32;
+
+        if self.powers.len() == self.n_windows {
+            self.powers.pop_front();
+        }
+        self.powers.push_back(power);
+        let mean = self.powers.iter().sum::<f32>() / self.powers.len() as f32;
+
+        // LUFS = -0.691 + 10 * log10(mean)
+        (10f32.powf(-0.0691) * mean).sqrt()
+    }
+}
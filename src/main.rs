@@ -1,11 +1,73 @@
-use anyhow::Result;
-use clap::Parser;
-use ears::{AudioController, Sound};
-use gtk::prelude::*;
-use libappindicator::{AppIndicator, AppIndicatorStatus};
+#[cfg(feature = "appindicator")]
+mod activity_log;
+#[cfg(feature = "appindicator")]
+mod app_indicator;
+mod bluetooth;
+mod calibrate;
+mod capture;
+mod classifier;
+mod config;
+mod control;
+mod controls;
+mod conversation;
+mod default_source;
+mod detector;
+mod fallback_icons;
+mod filter;
+mod format;
+mod haptic;
+mod hotplug;
+#[cfg(feature = "appindicator")]
+mod indicator;
+#[cfg(feature = "appindicator")]
+mod level_history;
+#[cfg(feature = "appindicator")]
+mod level_meter;
+mod linked;
+mod loudness;
+mod noise_floor;
+mod notifications;
+#[cfg(feature = "appindicator")]
+mod overlay;
+mod pattern;
+mod preferred;
+mod recorders;
+mod scan;
+mod schedule;
+mod session;
+#[cfg(feature = "appindicator")]
+mod settings;
+#[cfg(feature = "ksni")]
+mod sni;
+mod sound;
+mod sound_theme;
+mod source_list;
+#[cfg(feature = "spectral")]
+mod spectral;
+mod speech;
+mod tray;
+mod vad;
+#[cfg(feature = "appindicator")]
+mod wizard;
+
+#[cfg(not(any(feature = "appindicator", feature = "ksni")))]
+compile_error!("at least one tray backend must be enabled, with the appindicator or ksni feature");
+
+use anyhow::{Context as _, Result};
+use capture::Capture;
+use clap::parser::ValueSource;
+use clap::{CommandFactory, FromArgMatches};
+use classifier::{Classifier, SoundClass};
+use controls::Controls;
+use conversation::Conversation;
+use detector::DetectionMode;
+use format::SampleFormat;
+use haptic::Haptic;
+use libspa::param::audio::AudioInfoRaw;
 use libspa::pod::Pod;
 use libspa::utils::Direction;
 use libspa_sys::*;
+use noise_floor::NoiseFloor;
 use pipewire::context::Context;
 use pipewire::core::Core;
 use pipewire::keys;
@@ -13,57 +75,621 @@ use pipewire::loop_::Signal;
 use pipewire::main_loop::MainLoop;
 use pipewire::properties::properties;
 use pipewire::stream::{Stream, StreamFlags, StreamListener, StreamRef, StreamState};
-use std::mem::{size_of, zeroed};
-use std::sync::mpsc;
+use sound::{Overlap, Sound, Tone};
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeSet, HashMap};
+use std::mem::zeroed;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::{mpsc, Arc};
 use std::thread;
 use std::time::{Duration, Instant};
 
-#[derive(clap::Parser)]
+#[derive(Clone, clap::Parser)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     #[arg(long, default_value = "-60")]
     /// The input threshold volume in dB.
     threshold: f32,
 
-    #[arg(long, value_parser = |v: &str| -> Result<Duration> { Ok(Duration::from_millis(v.parse()?)) }, default_value="750")]
+    #[arg(long)]
+    /// The input volume in dB that turns the "on" state on. Defaults to --threshold.
+    threshold_on: Option<f32>,
+
+    #[arg(long)]
+    /// The input volume in dB that keeps the "on" state on. Defaults to --threshold.
+    threshold_off: Option<f32>,
+
+    #[arg(long)]
+    /// Raise the thresholds to this many dB above a slowly adapting estimate of the ambient noise floor.
+    adaptive_margin: Option<f32>,
+
+    #[arg(long, default_value = "0")]
+    /// Amplify the analyzed input by this many dB, for very quiet microphones. This raises all measured levels like --db-offset does, and adds to it.
+    capture_gain: f32,
+
+    #[arg(long, default_value = "0")]
+    /// Add this many dB to all measured levels, so that thresholds can be given on the scale of an external meter instead of in dBFS.
+    db_offset: f32,
+
+    #[arg(long, visible_alias = "attack", value_parser = |v: &str| -> Result<Duration> { Ok(Duration::from_millis(v.parse()?)) }, default_value="0")]
+    /// Require microphone input for this many milliseconds before switching to the "on" state.
+    attack_time: Duration,
+
+    #[arg(long, visible_alias = "hold", value_parser = |v: &str| -> Result<Duration> { Ok(Duration::from_millis(v.parse()?)) }, default_value="750")]
     /// Hold the "on" state this many milliseconds after microphone input stopped.
     hold_time: Duration,
 
+    #[arg(long, visible_alias = "release", value_parser = |v: &str| -> Result<Duration> { Ok(Duration::from_millis(v.parse()?)) }, default_value="0")]
+    /// After the hold time, wind down for this many milliseconds before switching to the "off" state.
+    release_time: Duration,
+
+    #[arg(long, value_parser = |v: &str| -> Result<Duration> { Ok(Duration::from_millis(v.parse()?)) }, default_value="0")]
+    /// Ignore bursts of input shorter than this many milliseconds, such as keyboard clicks, even while in the "on" state.
+    min_burst_time: Duration,
+
+    #[arg(long, value_parser = |v: &str| -> Result<Duration> { Ok(Duration::from_millis(v.parse()?)) }, default_value="0")]
+    /// Switch between the "on" and "off" states at most once per this many milliseconds.
+    min_toggle_interval: Duration,
+
+    #[arg(long, value_parser = |v: &str| -> Result<Duration> { Ok(Duration::from_millis(v.parse()?)) }, default_value="0")]
+    /// Ignore the microphone input while a sound plays and for this many milliseconds after it, so that the microphone does not pick it up.
+    feedback_suppression: Duration,
+
+    #[arg(long, value_parser = |v: &str| -> Result<Duration> { Ok(Duration::try_from_secs_f32(v.parse()?)?) }, default_value="0")]
+    /// Play the on and off sounds at most once per this many seconds, even if the state toggles more often.
+    sound_cooldown: Duration,
+
+    #[arg(long, value_parser = |v: &str| -> Result<Duration> { Ok(Duration::from_millis(v.parse()?)) })]
+    /// Smooth the measured level with an exponential moving average using this time constant in milliseconds.
+    smoothing: Option<Duration>,
+
+    #[arg(long, value_parser = |v: &str| -> Result<Duration> { Ok(Duration::from_millis(v.parse()?)) }, default_value="20")]
+    /// Analyze the input in windows of this many milliseconds, independent of the PipeWire quantum.
+    window: Duration,
+
+    #[arg(long, value_parser = |v: &str| -> Result<Duration> { Ok(Duration::from_millis(v.parse()?)) }, default_value="0")]
+    /// Fade sounds in and out over this many milliseconds, to avoid clicks from sounds that do not start or end at zero.
+    sound_fade: Duration,
+
     #[arg(long)]
-    /// Sound to play when microphone input is detected.
+    /// Play sounds to this sink, given by node name or object serial, instead of the default sink.
+    sound_sink: Option<String>,
+
+    #[arg(long, value_enum, default_value = "mix")]
+    /// What to do when a sound should be played while another one is still playing.
+    sound_overlap: Overlap,
+
+    #[arg(long, value_parser = parse_volume, default_value = "1", allow_hyphen_values = true)]
+    /// Play sounds at this volume, given either between 0 and 1 or in dB (e.g. -6dB).
+    volume: f32,
+
+    #[arg(long)]
+    /// Sound to play when microphone input is detected. If this is a directory, a random file from it is played each time.
     on_sound: Option<String>,
 
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..), default_value = "1")]
+    /// Play the on sound this many times in a row.
+    on_sound_repeat: u32,
+
+    #[arg(long, value_parser = parse_millis, default_value = "200")]
+    /// Leave this much time between repetitions of the on sound, in milliseconds or with a unit like 200ms or 0.2s.
+    on_sound_gap: Duration,
+
+    #[arg(long)]
+    /// Do not play the built-in clicks when no --on-sound or --off-sound is given.
+    no_default_sounds: bool,
+
     #[arg(long)]
     /// Sound to play when no microphone input is detected anymore.
     off_sound: Option<String>,
+
+    #[arg(long, value_parser = |v: &str| -> Result<Duration> { Ok(Duration::from_millis(v.parse()?)) }, default_value="0")]
+    /// Only play the off sound once no microphone input was detected for this many more milliseconds after the hold time.
+    off_sound_delay: Duration,
+
+    #[arg(long, value_parser = |v: &str| v.parse::<Tone>(), num_args = 0..=1, default_missing_value = "880:50ms", conflicts_with = "on_sound")]
+    /// Play a synthesized beep when microphone input is detected, given as frequency:duration[:square] (e.g. 880:50ms).
+    on_tone: Option<Tone>,
+
+    #[arg(long, value_parser = |v: &str| v.parse::<Tone>(), num_args = 0..=1, default_missing_value = "660:50ms", conflicts_with = "off_sound")]
+    /// Play a synthesized beep when no microphone input is detected anymore, given like --on-tone.
+    off_tone: Option<Tone>,
+
+    #[arg(long)]
+    /// Sound to play when the capture stream of a source that is on is suspended, e.g. because the device went idle. Defaults to the off sound.
+    suspended_sound: Option<String>,
+
+    #[arg(long)]
+    /// Sound to play when the capture stream fails or no device is available anymore, so that monitoring stopped.
+    error_sound: Option<String>,
+
+    #[arg(long)]
+    /// Sound to play when the microphone input is clipping.
+    clipping_sound: Option<String>,
+
+    #[arg(long)]
+    /// Announce when the microphone turns on and off through speech-dispatcher, in addition to any sounds.
+    announce: bool,
+
+    #[arg(long)]
+    /// Pulse this force-feedback device (e.g. /dev/input/event5 of a gamepad) when the microphone turns on or off.
+    haptic_device: Option<String>,
+
+    #[arg(long)]
+    /// Sound to play when microphone input is detected while the source is muted.
+    muted_sound: Option<String>,
+
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..), default_value = "1")]
+    /// Play the muted sound this many times in a row.
+    muted_sound_repeat: u32,
+
+    #[arg(long, value_parser = parse_millis, default_value = "200")]
+    /// Leave this much time between repetitions of the muted sound, in milliseconds or with a unit like 200ms or 0.2s.
+    muted_sound_gap: Duration,
+
+    #[arg(long)]
+    /// Sound to play when another application starts capturing audio.
+    recording_sound: Option<String>,
+
+    #[arg(long)]
+    /// Sound to play when a Bluetooth headset switches to a profile that uses its microphone.
+    headset_sound: Option<String>,
+
+    #[arg(long)]
+    /// Sound to play when an application starts using a camera.
+    camera_on_sound: Option<String>,
+
+    #[arg(long)]
+    /// Sound to play when no application uses a camera anymore.
+    camera_off_sound: Option<String>,
+
+    #[arg(long)]
+    /// Sound to play periodically while the microphone is on, as a reminder that it is still on.
+    reminder_sound: Option<String>,
+
+    #[arg(long, value_parser = |v: &str| -> Result<Duration> { Ok(Duration::try_from_secs_f32(v.parse()?)?) }, default_value="60")]
+    /// Play the reminder sound every this many seconds.
+    reminder_interval: Duration,
+
+    #[arg(long, value_parser = |v: &str| -> Result<Duration> { Ok(Duration::try_from_secs_f32(v.parse::<f32>()? * 60.)?) }, default_value="0")]
+    /// Only start playing the reminder sound once the microphone was on for this many minutes.
+    reminder_after: Duration,
+
+    #[arg(long)]
+    /// While the microphone is on, input below this level in dB is considered quiet.
+    quiet_level: Option<f32>,
+
+    #[arg(long)]
+    /// While the microphone is on, input above this level in dB is considered loud.
+    loud_level: Option<f32>,
+
+    #[arg(long)]
+    /// Sound to play when the microphone input becomes loud.
+    loud_sound: Option<String>,
+
+    #[arg(long, value_enum, default_value = "peak", visible_alias = "detector")]
+    /// How to measure the input level of each captured buffer.
+    detection_mode: DetectionMode,
+
+    #[arg(long, value_parser = |v: &str| -> Result<Duration> { Ok(Duration::from_secs(v.parse()?)) })]
+    /// Only report the microphone as on once speech has been going on for this many seconds in total, i.e. during a conversation.
+    conversation: Option<Duration>,
+
+    #[arg(long, value_parser = |v: &str| -> Result<Duration> { Ok(Duration::from_secs(v.parse()?)) }, default_value="30")]
+    /// Consider a conversation over after this many seconds without speech.
+    conversation_gap: Duration,
+
+    #[arg(long)]
+    /// Only switch to the "on" state for speech-like sounds, ignoring impulsive sounds like keyboard clicks and steady noise like fans.
+    classify: bool,
+
+    #[arg(long)]
+    /// Remove any DC offset from the input before measuring its level.
+    dc_block: bool,
+
+    #[arg(long)]
+    /// Apply a high-pass filter with this cutoff frequency in Hz before measuring the input level.
+    highpass: Option<f32>,
+
+    #[arg(long)]
+    /// Only consider the voice band (roughly 200 to 4000 Hz) when measuring the input level.
+    voice_band: bool,
+
+    #[arg(long)]
+    /// Apply A-weighting to the input, so that the threshold roughly follows perceived loudness.
+    a_weighting: bool,
+
+    #[arg(long, visible_alias = "channel", value_delimiter = ',')]
+    /// Only consider these channels (counting from 0) of the source. Defaults to all channels.
+    channels: Vec<u32>,
+
+    #[arg(long = "target")]
+    /// Monitor the source with this node name or object serial, instead of the default source. Can be given multiple times to monitor several sources.
+    targets: Vec<String>,
+
+    #[arg(long, conflicts_with = "targets")]
+    /// Monitor the source with this node name or object serial instead of the default source while it is present. Can be given multiple times, in order of preference.
+    prefer: Vec<String>,
+
+    #[arg(long, conflicts_with = "targets")]
+    /// Monitor the echo-cancelled source of PipeWire's echo-cancel module while it exists, so that sound from the speakers is not picked up.
+    echo_cancel: bool,
+
+    #[arg(long)]
+    /// Monitor the playback on the default sink (or the sink given by --target) instead of a source.
+    monitor_sink: bool,
+
+    #[arg(long)]
+    /// Watch the mute state of the node with this name instead of the monitored one, e.g. a virtual source that applications record from. PipeWire silences a muted source for every stream, so talking while muted can only be detected on a node before the muted one.
+    mute_target: Option<String>,
+
+    #[arg(long)]
+    /// Never monitor nodes whose name matches this pattern, where * matches any text (e.g. "*.monitor"). Can be given multiple times.
+    exclude: Vec<String>,
+
+    #[arg(long, value_parser = parse_latency)]
+    /// Request this latency for the capture stream, given as quantum/rate (e.g. 1024/48000). Larger values mean fewer wakeups.
+    latency: Option<String>,
+
+    #[arg(long, conflicts_with = "channels")]
+    /// Request a mono capture stream, letting PipeWire downmix all channels of the source.
+    mono: bool,
+
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    /// Request a capture stream with this sample rate in Hz, e.g. 16000 to save CPU time.
+    rate: Option<u32>,
+
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..), default_value = "1")]
+    /// Only analyze every Nth captured buffer, saving CPU time at the cost of accuracy.
+    decimation: u32,
+
+    #[arg(long)]
+    /// Print the input level in dB to stdout several times per second.
+    meter: bool,
+
+    #[arg(long, value_enum, default_value_t)]
+    /// Which implementation of the tray icon to use.
+    tray_backend: tray::TrayBackend,
+
+    #[arg(long)]
+    /// Show the input level in dB next to the tray icon and in its tooltip.
+    tray_level: bool,
+
+    #[arg(long)]
+    /// Show how long the microphone has been on next to the tray icon and in its tooltip.
+    talk_timer: bool,
+
+    #[arg(long, value_enum)]
+    /// Show the tray icon in this corner of the screen while the microphone is on, on Wayland compositors with wlr-layer-shell.
+    overlay: Option<tray::Corner>,
+
+    #[arg(long)]
+    /// Show the tray icon in a small window that stays above other windows, for desktops without a tray.
+    window_indicator: bool,
+
+    #[arg(long)]
+    /// Offer a D-Bus interface on the session bus, whose TogglePause and ToggleSilenced methods work like SIGUSR1 and SIGUSR2.
+    dbus_control: bool,
+
+    #[arg(long, value_enum, value_delimiter = ',')]
+    /// Show desktop notifications for these events, e.g. --notify device-lost,muted,recording.
+    notify: Vec<notifications::NotifyEvent>,
+
+    #[arg(long)]
+    /// The name of the tray icon while the microphone is on. Defaults to microphone-sensitivity-high-symbolic.
+    icon_active: Option<String>,
+
+    #[arg(long)]
+    /// The name of the tray icon while the microphone is off. Defaults to microphone-sensitivity-low-symbolic.
+    icon_inactive: Option<String>,
+
+    #[arg(long)]
+    /// The name of the tray icon while the capture stream is suspended. Defaults to microphone-sensitivity-muted-symbolic.
+    icon_suspended: Option<String>,
+
+    #[arg(long, default_value = "20")]
+    /// Let the peak-hold value of the level meter decay by this many dB per second.
+    peak_decay: f32,
+
+    #[arg(long)]
+    /// Connect to the PipeWire instance with this remote name (e.g. pipewire-1) or socket path, instead of the default one.
+    remote: Option<String>,
+
+    #[arg(long)]
+    /// Suspend monitoring and stay silent while the session is locked.
+    pause_when_locked: bool,
+
+    #[arg(long)]
+    /// Read additional settings from this file. Defaults to ~/.config/pw-micclick/config.toml.
+    config: Option<PathBuf>,
+}
+
+/// Check that a latency is given as quantum/rate, like PipeWire expects it.
+fn parse_latency(value: &str) -> Result<String> {
+    let Some((quantum, rate)) = value.split_once('/') else {
+        anyhow::bail!("expected a latency like 1024/48000");
+    };
+    let _: (u32, u32) = (quantum.parse()?, rate.parse()?);
+    Ok(value.to_owned())
+}
+
+/// Parse a duration given in milliseconds, either bare (like "200") or with a
+/// unit (like "200ms" or "0.2s").
+fn parse_millis(value: &str) -> Result<Duration> {
+    let seconds = match value.strip_suffix("ms") {
+        Some(ms) => ms.trim().parse::<f64>()? / 1000.,
+        None => match value.strip_suffix('s') {
+            Some(secs) => secs.trim().parse::<f64>()?,
+            None => value.parse::<f64>()? / 1000.,
+        },
+    };
+    Ok(Duration::try_from_secs_f64(seconds)?)
 }
 
+/// Parse a volume given either in dB (like "-6dB") or as a linear factor between 0 and 1.
+fn parse_volume(value: &str) -> Result<f32> {
+    if let Some(db) = value.strip_suffix("dB").or(value.strip_suffix("db")) {
+        let db: f32 = db.trim().parse()?;
+        // Like the linear volume, this can only make the sounds quieter.
+        if db.is_nan() || db > 0. {
+            anyhow::bail!("expected a volume of at most 0dB, like -6dB");
+        }
+        return Ok(10f32.powf(db / 20.));
+    }
+    let volume: f32 = value.parse()?;
+    if !(0. ..=1.).contains(&volume) {
+        anyhow::bail!("expected a volume between 0 and 1, or in dB like -6dB");
+    }
+    Ok(volume)
+}
+
+impl Args {
+    /// The sources given by --prefer, preceded by the echo-cancelled source if requested.
+    fn preferred_sources(&self) -> Vec<String> {
+        let mut sources = Vec::new();
+        if self.echo_cancel {
+            sources.push(ECHO_CANCEL_SOURCE.to_owned());
+        }
+        sources.extend(self.prefer.iter().cloned());
+        sources
+    }
+
+    /// The number of dB added to all measured levels, by both --db-offset and
+    /// --capture-gain.
+    fn offset(&self) -> f32 {
+        self.db_offset + self.capture_gain
+    }
+
+    /// Convert a level in dB, as given on the command line, to a linear amplitude.
+    fn linear(&self, db: f32) -> f32 {
+        10f32.powf((db - self.offset()) / 20.)
+    }
+
+    /// Convert a linear amplitude to a level in dB, on the scale of the command line.
+    fn db(&self, level: f32) -> f32 {
+        20. * level.max(f32::MIN_POSITIVE).log10() + self.offset()
+    }
+}
+
+#[derive(Clone, clap::Subcommand)]
+enum Command {
+    /// Measure the noise floor and speech levels, and suggest a matching threshold and hold time.
+    Calibrate {
+        #[arg(long, value_parser = |v: &str| -> Result<Duration> { Ok(Duration::from_secs(v.parse()?)) }, default_value="5")]
+        /// Record silence and speech for this many seconds each.
+        duration: Duration,
+    },
+}
+
+/// The node name of the source created by PipeWire's echo-cancel module.
+const ECHO_CANCEL_SOURCE: &str = "echo-cancel-source";
+
+/// Lower and upper cutoff frequencies in Hz used for `--voice-band`.
+const VOICE_BAND: (f32, f32) = (200., 4000.);
+
+/// Identifies a monitored source by its position in the list of `--target`s.
+type SourceId = usize;
+
+/// Requests from other threads, which are handled on the main thread.
 #[derive(Debug, Copy, Clone)]
+enum Request {
+    /// Pause or resume monitoring on behalf of the user.
+    TogglePause,
+    /// Pause (true) or resume (false) monitoring on behalf of the user.
+    Pause(bool),
+    /// Mute or unmute all sounds on behalf of the user.
+    ToggleSilenced,
+    /// The session was locked or unlocked.
+    Locked(bool),
+    /// Use a new on threshold in dB, from the settings window, and shift the
+    /// off threshold with it.
+    SetThreshold(f32),
+    /// Monitor the node with the given id instead of the first source, or go
+    /// back to choosing it automatically with None.
+    SelectSource(Option<u32>),
+    /// Load the configuration file again, applying only the thresholds, the
+    /// hold time, the schedule and the quiet hours.
+    ReloadConfig,
+    /// Stop the main loop and exit.
+    Quit,
+}
+
+/// An event, together with the source it concerns.
+#[derive(Debug, Clone)]
+struct Event {
+    /// The source, or None for events that concern no particular source.
+    source: Option<SourceId>,
+    event: MicEvent,
+}
+
+/// The channels to all threads that handle events.
+#[derive(Clone)]
+struct Queues {
+    /// The tray thread's channel, which wakes up its main context.
+    tray: async_channel::Sender<Event>,
+    others: Vec<mpsc::Sender<Event>>,
+}
+
+impl Queues {
+    fn send(&self, source: Option<SourceId>, event: MicEvent) {
+        self.tray
+            .try_send(Event {
+                source,
+                event: event.clone(),
+            })
+            .expect("cannot send: channel broken");
+        for q in self.others.iter() {
+            q.send(Event {
+                source,
+                event: event.clone(),
+            })
+            .expect("cannot send: channel broken");
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 enum MicEvent {
     Active,
+    Releasing,
     Inactive,
     Suspended,
+    /// There is no device to capture from.
+    NoDevice,
+    /// The capture stream failed.
+    Error,
+    /// The source was muted, in hardware or in software.
+    Muted,
+    Unmuted,
+    /// Another application, given by its name, started capturing audio.
+    RecordingStarted(Arc<str>),
+    /// Another application, given by its name, stopped capturing audio.
+    RecordingStopped(Arc<str>),
+    /// A Bluetooth headset, given by its name, switched to a profile that uses its microphone.
+    HeadsetMicOn(Arc<str>),
+    /// A Bluetooth headset, given by its name, switched back to a profile without microphone.
+    HeadsetMicOff(Arc<str>),
+    /// Another application, given by its name, started capturing video from a camera.
+    CameraStarted(Arc<str>),
+    /// Another application, given by its name, stopped capturing video.
+    CameraStopped(Arc<str>),
+    /// Monitoring was paused or resumed.
+    Paused(bool),
+    /// The user muted (true) or unmuted (false) all sounds.
+    Silenced(bool),
+    /// The user asked to hear the on (true) or off (false) sound.
+    TestSound(bool),
+    /// A source that can be selected in the tray appeared or disappeared.
+    SourcesChanged,
+    Clipping,
+    Loudness(Loudness),
+    Level(Meter),
+}
+
+/// The current input level and its peak-hold value, as linear amplitudes.
+#[derive(Debug, Copy, Clone)]
+struct Meter {
+    level: f32,
+    peak: f32,
+    /// The level before it is gated by speech detection, classification and
+    /// feedback suppression, and before smoothing.
+    ungated: f32,
+}
+
+/// How often a `MicEvent::Level` is sent.
+const METER_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How loud the microphone input is while it is on.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Loudness {
+    Quiet,
+    Normal,
+    Loud,
+}
+
+/// How long the input has to stay in a different loudness tier before it is reported.
+const LOUDNESS_SETTLE_TIME: Duration = Duration::from_millis(250);
+
+/// Samples with at least this absolute value are considered to be clipping.
+const CLIPPING_LEVEL: f32 = 0.999;
+/// Number of consecutive clipping analysis windows before a `MicEvent::Clipping` is sent.
+const CLIPPING_WINDOWS: u32 = 3;
+/// How long the tray icon shows a warning after clipping was detected.
+const CLIPPING_WARNING_TIME: Duration = Duration::from_secs(2);
+
+/// The tray icon that is shown while the source is muted.
+const MUTED_ICON: &str = "audio-input-microphone-muted-symbolic";
+/// How long the tray icon shows each of the muted and the warning icon, while flashing
+/// because of input on a muted source.
+const MUTED_FLASH_TIME: Duration = Duration::from_millis(500);
+
+/// How many dB the tray menu raises or lowers the thresholds by at a time.
+const THRESHOLD_STEP: f32 = 3.;
+/// How long the tray icon's label shows the threshold after it was adjusted.
+const THRESHOLD_NOTICE_TIME: Duration = Duration::from_secs(2);
+
+/// How often to check whether a different schedule entry applies.
+const SCHEDULE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// The phases of the gate envelope that decides whether the microphone is "on".
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Gate {
+    Closed,
+    Open,
+    Releasing,
 }
 
 struct CaptureState {
-    queues: Vec<mpsc::Sender<MicEvent>>,
-    threshold: f32,
-    hold_time: Duration,
-    falloff: Instant,
-    is_on: bool,
+    queues: Queues,
+    source: SourceId,
+    controls: Arc<Controls>,
+    adaptive_margin: Option<f32>,
+    noise_floor: NoiseFloor,
+    attack_time: Duration,
+    release_time: Duration,
+    min_burst_time: Duration,
+    min_toggle_interval: Duration,
+    last_toggle: Option<Duration>,
+    smoothing: Option<Duration>,
+    smoothed_level: f32,
+    classifier: Option<Classifier>,
+    capture: Capture,
+    decimation: u32,
+    buffer_count: u32,
+    clipping_windows: u32,
+    window_duration: Duration,
+    time: Duration,
+    onset: Option<Duration>,
+    falloff: Duration,
+    gate: Gate,
+    conversation: Option<Conversation>,
+    quiet_level: f32,
+    loud_level: f32,
+    loudness: Loudness,
+    loudness_since: Option<Duration>,
+    /// The factor by which the peak-hold value decays per analysis window.
+    peak_decay: f32,
+    meter_peak: f32,
+    meter_sent: Duration,
 }
 
 fn main() -> Result<()> {
-    let args = Args::parse();
-
-    let (tray_sender, tray_receiver) = mpsc::channel();
-    let _tray_thread = thread::spawn(move || tray_thread_main(tray_receiver));
-    let (clicker_sender, clicker_receiver) = mpsc::channel();
-    let _clicker_thread =
-        thread::spawn(move || clicker_thread_main(clicker_receiver, args.on_sound, args.off_sound));
+    let matches = Args::command().get_matches();
+    let mut args = Args::from_arg_matches(&matches)?;
+    // Whether an argument with a default value was given on the command line.
+    let given = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
 
     let mainloop = MainLoop::new(None)?;
     let context = Context::new(&mainloop)?;
-    let core = context.connect(None)?;
+    let core = match args.remote {
+        Some(ref remote) => context
+            .connect(Some(properties! { *keys::REMOTE_NAME => remote.as_str() }))
+            .with_context(|| format!("cannot connect to PipeWire remote {remote}"))?,
+        None => context.connect(None)?,
+    };
 
     let _sigint = mainloop.loop_().add_signal_local(Signal::SIGINT, {
         let mainloop = mainloop.clone();
@@ -74,60 +700,763 @@ fn main() -> Result<()> {
         move || mainloop.quit()
     });
 
-    let senders = vec![tray_sender, clicker_sender];
-    let _capture = create_capture(&core, senders, args.threshold, args.hold_time)?;
+    if let Some(Command::Calibrate { duration }) = args.command {
+        return calibrate::calibrate(&mainloop, &core, &args, duration);
+    }
+
+    let config = config::load(args.config.as_deref())?;
+    if let Some(threshold) = config.threshold.filter(|_| !given("threshold")) {
+        args.threshold = threshold;
+    }
+    if let Some(hold_time) = config.hold_time.filter(|_| !given("hold_time")) {
+        args.hold_time = Duration::from_millis(hold_time);
+    }
+    if args.on_sound.is_none() && args.on_tone.is_none() {
+        args.on_sound = config.on_sound;
+    }
+    if args.off_sound.is_none() && args.off_tone.is_none() {
+        args.off_sound = config.off_sound;
+    }
+    if args.targets.is_empty() {
+        args.targets.extend(config.target);
+        // Clap only checks the conflict for a target from the command line.
+        if !args.targets.is_empty() && (!args.prefer.is_empty() || args.echo_cancel) {
+            anyhow::bail!(
+                "the target in the config file cannot be combined with --prefer or --echo-cancel"
+            );
+        }
+    }
+    if args.suspended_sound.is_none() {
+        args.suspended_sound = config.suspended_sound;
+    }
+    args.icon_active = args.icon_active.or(config.icons.active);
+    args.icon_inactive = args.icon_inactive.or(config.icons.inactive);
+    args.icon_suspended = args.icon_suspended.or(config.icons.suspended);
+    let threshold_on = args.threshold_on.unwrap_or(args.threshold);
+    let threshold_off = args.threshold_off.unwrap_or(args.threshold);
+    if threshold_off > threshold_on {
+        anyhow::bail!("--threshold-off must not be higher than --threshold-on");
+    }
+    if args.reminder_interval.is_zero() {
+        anyhow::bail!("--reminder-interval must not be zero");
+    }
+    #[cfg(not(feature = "layer-shell"))]
+    if args.overlay.is_some() {
+        anyhow::bail!("--overlay requires building with the \"layer-shell\" feature");
+    }
+    #[cfg(not(feature = "appindicator"))]
+    if args.window_indicator {
+        anyhow::bail!("--window-indicator requires building with the \"appindicator\" feature");
+    }
+    #[cfg(feature = "appindicator")]
+    if (args.overlay.is_some() || args.window_indicator)
+        && args.tray_backend != tray::TrayBackend::Appindicator
+    {
+        anyhow::bail!("--overlay and --window-indicator require --tray-backend appindicator");
+    }
+    let controls = Arc::new(Controls::new(
+        args.linear(threshold_on),
+        args.linear(threshold_off),
+        args.hold_time,
+    ));
+
+    // The thresholds that apply outside of the schedule, which the settings window can change.
+    let base_thresholds = Rc::new(Cell::new((threshold_on, threshold_off)));
+    // The schedule and the quiet hours, which are replaced when the configuration is reloaded.
+    let schedule = Rc::new(RefCell::new(config.schedule));
+    let quiet_hours = Rc::new(RefCell::new(config.quiet_hours));
+    let apply_schedule = Rc::new({
+        let controls = controls.clone();
+        let base_thresholds = base_thresholds.clone();
+        let schedule = schedule.clone();
+        let quiet_hours = quiet_hours.clone();
+        let db_offset = args.offset();
+        let linear = move |db: f32| 10f32.powf((db - db_offset) / 20.);
+        move || {
+            let now = match schedule::TimeOfDay::now() {
+                Ok(now) => now,
+                Err(e) => {
+                    eprintln!("error: cannot get the time of day: {e}");
+                    return;
+                }
+            };
+            let (threshold_on, threshold_off) = base_thresholds.get();
+            let (on, off, sounds) = match schedule::active(&schedule.borrow(), now) {
+                Some(entry) => {
+                    let on = entry.threshold_on().unwrap_or(threshold_on);
+                    let off = entry.threshold_off().unwrap_or(threshold_off).min(on);
+                    (on, off, entry.sounds)
+                }
+                None => (threshold_on, threshold_off, true),
+            };
+            controls.set_thresholds(linear(on), linear(off));
+            controls.set_sounds(sounds && !quiet_hours.borrow().iter().any(|q| q.contains(now)));
+        }
+    });
+    apply_schedule();
+    let schedule_timer = mainloop.loop_().add_timer({
+        let apply_schedule = apply_schedule.clone();
+        move |_| apply_schedule()
+    });
+    schedule_timer
+        .update_timer(Some(SCHEDULE_INTERVAL), Some(SCHEDULE_INTERVAL))
+        .into_result()?;
+
+    let labels: Vec<String> = if args.targets.is_empty() {
+        vec!["default".to_owned()]
+    } else {
+        args.targets.clone()
+    };
+    let source_list: Arc<source_list::SourceList> = Arc::default();
+    let (request_sender, request_receiver) = pipewire::channel::channel();
+    let (tray_sender, tray_receiver) = async_channel::unbounded();
+    let (clicker_sender, clicker_receiver) = mpsc::channel();
+    let _tray_thread = thread::spawn({
+        let labels = labels.clone();
+        let request_sender = request_sender.clone();
+        let controls = controls.clone();
+        let clicker_sender = clicker_sender.clone();
+        let source_list = source_list.clone();
+        let config_path = args.config.clone().unwrap_or_else(config::default_path);
+        let args = args.clone();
+        move || {
+            tray::thread_main(
+                args.tray_backend,
+                tray_receiver,
+                tray::TrayState::new(labels, args),
+                tray::TrayActions::new(
+                    request_sender,
+                    controls,
+                    clicker_sender,
+                    source_list,
+                    config_path,
+                ),
+            )
+        }
+    });
+    let (player_sender, player_receiver) = pipewire::channel::channel();
+    let _clicker_thread = thread::spawn({
+        let controls = controls.clone();
+        let args = args.clone();
+        let sources: Vec<Option<config::SourceSounds>> = labels
+            .iter()
+            .map(|label| config.sources.get(label).cloned())
+            .collect();
+        move || clicker_thread_main(clicker_receiver, player_sender, controls, &args, sources)
+    });
+    let player = sound::Player::new(
+        &core,
+        args.volume,
+        args.sound_fade,
+        args.sound_sink.clone(),
+        args.sound_overlap,
+    );
+    let _player_receiver = player_receiver.attach(mainloop.loop_(), move |sound| {
+        if let Err(e) = player.play(sound) {
+            eprintln!("error: cannot play sound: {e}");
+        }
+    });
 
+    let mut queues = vec![clicker_sender];
+    if args.meter {
+        let (meter_sender, meter_receiver) = mpsc::channel();
+        let db_offset = args.offset();
+        let labels = labels.clone();
+        thread::spawn(move || meter_thread_main(meter_receiver, labels, db_offset));
+        queues.push(meter_sender);
+    }
+    if !args.notify.is_empty() {
+        let (notify_sender, notify_receiver) = mpsc::channel();
+        let labels = labels.clone();
+        let events = args.notify.clone();
+        let request_sender = request_sender.clone();
+        thread::spawn(move || {
+            notifications::thread_main(notify_receiver, labels, events, request_sender)
+        });
+        queues.push(notify_sender);
+    }
+    let queues = Queues {
+        tray: tray_sender,
+        others: queues,
+    };
+    let monitors = Rc::new(if args.targets.is_empty() {
+        vec![monitor_source(&core, &queues, &controls, &args, 0, None)?]
+    } else {
+        args.targets
+            .iter()
+            .enumerate()
+            .map(|(source, target)| {
+                monitor_source(&core, &queues, &controls, &args, source, Some(target))
+            })
+            .collect::<Result<Vec<_>>>()?
+    });
+    let _source_watcher = source_list::watch(
+        &core,
+        if args.monitor_sink {
+            "Audio/Sink"
+        } else {
+            "Audio/Source"
+        },
+        args.exclude.clone(),
+        source_list.clone(),
+        {
+            let queues = queues.clone();
+            let monitors = monitors.clone();
+            let request_sender = request_sender.clone();
+            move || {
+                // A node that was removed never comes back with the same id,
+                // so the tray's selection is gone along with it.
+                let selected = monitors[0].selected.get();
+                if selected.is_some_and(|id| !source_list.contains(id)) {
+                    eprintln!("the device selected in the tray was removed");
+                    let _ = request_sender.send(Request::SelectSource(None));
+                }
+                queues.send(None, MicEvent::SourcesChanged);
+            }
+        },
+    )?;
+    if args.pause_when_locked {
+        let request_sender = request_sender.clone();
+        thread::spawn(move || {
+            session::thread_main(move |locked| {
+                let _ = request_sender.send(Request::Locked(locked));
+            })
+        });
+    }
+    let _request_receiver = request_receiver.attach(mainloop.loop_(), {
+        let mainloop = mainloop.clone();
+        let args = args.clone();
+        let controls = controls.clone();
+        let monitors = monitors.clone();
+        let queues = queues.clone();
+        let paused_by_user = Cell::new(false);
+        let locked = Cell::new(false);
+        let threshold_given = given("threshold");
+        let hold_time_given = given("hold_time");
+        move |request| {
+            match request {
+                Request::TogglePause => {
+                    let paused = !paused_by_user.get();
+                    eprintln!("{} monitoring", if paused { "pausing" } else { "resuming" });
+                    paused_by_user.set(paused);
+                }
+                Request::Pause(paused) => {
+                    eprintln!("{} monitoring", if paused { "pausing" } else { "resuming" });
+                    paused_by_user.set(paused);
+                }
+                Request::Locked(is_locked) => {
+                    eprintln!("session {}", if is_locked { "locked" } else { "unlocked" });
+                    locked.set(is_locked);
+                }
+                Request::SetThreshold(threshold) => {
+                    let (on, off) = base_thresholds.get();
+                    base_thresholds.set((threshold, threshold - (on - off)));
+                    apply_schedule();
+                    return;
+                }
+                Request::SelectSource(id) => {
+                    // The tray only offers a choice while a single source is monitored.
+                    let [monitor] = &monitors[..] else {
+                        return;
+                    };
+                    monitor.selected.set(id);
+                    let id = match id {
+                        Some(id) => {
+                            eprintln!("switching to the device selected in the tray");
+                            Some(id)
+                        }
+                        None => {
+                            eprintln!("switching back to the automatically chosen device");
+                            monitor.preferred.as_ref().and_then(|p| p.best())
+                        }
+                    };
+                    let (ref stream, _) = *monitor.capture;
+                    if let Err(e) = reconnect_capture(stream, &args, &controls, id) {
+                        eprintln!("error: cannot reconnect capture stream: {e}");
+                    }
+                    return;
+                }
+                Request::ReloadConfig => {
+                    let config = match config::load(args.config.as_deref()) {
+                        Ok(config) => config,
+                        Err(e) => {
+                            eprintln!("error: cannot reload the configuration: {e:#}");
+                            return;
+                        }
+                    };
+                    eprintln!(
+                        "reloading the thresholds, hold time, schedule and quiet hours, \
+                         other settings need a restart"
+                    );
+                    // Like at startup, --threshold-on and --threshold-off take
+                    // precedence over the threshold from the file.
+                    if let Some(threshold) = config.threshold.filter(|_| !threshold_given) {
+                        let (on, off) = base_thresholds.get();
+                        let on = args.threshold_on.map_or(threshold, |_| on);
+                        let off = args.threshold_off.map_or(threshold, |_| off);
+                        if off > on {
+                            eprintln!(
+                                "error: the threshold in the config file must not be lower than \
+                                 --threshold-off, keeping the thresholds"
+                            );
+                        } else {
+                            base_thresholds.set((on, off));
+                        }
+                    }
+                    if let Some(hold_time) = config.hold_time.filter(|_| !hold_time_given) {
+                        controls.set_hold_time(Duration::from_millis(hold_time));
+                    }
+                    *schedule.borrow_mut() = config.schedule;
+                    *quiet_hours.borrow_mut() = config.quiet_hours;
+                    apply_schedule();
+                    return;
+                }
+                Request::ToggleSilenced => {
+                    let silenced = controls.toggle_silenced();
+                    eprintln!("{} clicks", if silenced { "muting" } else { "unmuting" });
+                    queues.send(None, MicEvent::Silenced(silenced));
+                    return;
+                }
+                Request::Quit => {
+                    mainloop.quit();
+                    return;
+                }
+            }
+            let paused = paused_by_user.get() || locked.get();
+            if paused == controls.paused() {
+                return;
+            }
+            controls.set_paused(paused);
+            for monitor in monitors.iter() {
+                if let Err(e) = monitor.capture.0.set_active(!paused) {
+                    eprintln!("error: cannot pause or resume capture stream: {e}");
+                }
+            }
+            queues.send(None, MicEvent::Paused(paused));
+        }
+    });
+    let _sigusr1 = mainloop.loop_().add_signal_local(Signal::SIGUSR1, {
+        let request_sender = request_sender.clone();
+        move || {
+            let _ = request_sender.send(Request::TogglePause);
+        }
+    });
+    let _sigusr2 = mainloop.loop_().add_signal_local(Signal::SIGUSR2, {
+        let request_sender = request_sender.clone();
+        move || {
+            let _ = request_sender.send(Request::ToggleSilenced);
+        }
+    });
+    if args.dbus_control {
+        let request_sender = request_sender.clone();
+        thread::spawn(move || control::thread_main(request_sender));
+    }
+    let _recorders = recorders::watch(&core, "Stream/Input/Audio", {
+        let queues = queues.clone();
+        move |app, started| {
+            eprintln!(
+                "{app} {} capturing audio",
+                if started { "started" } else { "stopped" }
+            );
+            let event = if started {
+                MicEvent::RecordingStarted(app)
+            } else {
+                MicEvent::RecordingStopped(app)
+            };
+            queues.send(None, event);
+        }
+    })?;
+    let _cameras = recorders::watch(&core, "Stream/Input/Video", {
+        let queues = queues.clone();
+        move |app, started| {
+            eprintln!(
+                "{app} {} using a camera",
+                if started { "started" } else { "stopped" }
+            );
+            let event = if started {
+                MicEvent::CameraStarted(app)
+            } else {
+                MicEvent::CameraStopped(app)
+            };
+            queues.send(None, event);
+        }
+    })?;
+    let _headsets = bluetooth::watch(&core, {
+        let queues = queues.clone();
+        move |headset, on| {
+            eprintln!(
+                "{headset} switched to a profile {} its microphone",
+                if on { "with" } else { "without" }
+            );
+            let event = if on {
+                MicEvent::HeadsetMicOn(headset)
+            } else {
+                MicEvent::HeadsetMicOff(headset)
+            };
+            queues.send(None, event);
+        }
+    })?;
     mainloop.run();
 
     Ok(())
 }
 
+/// The streams and watchers that monitor one source.
+struct Monitor {
+    capture: Rc<(Stream, StreamListener<CaptureState>)>,
+    /// The node that was selected in the tray, which overrides the automatic choice.
+    selected: Rc<Cell<Option<u32>>>,
+    _default_source: Option<default_source::DefaultSourceWatcher>,
+    preferred: Option<Rc<preferred::PreferenceWatcher>>,
+    _devices: Rc<hotplug::DeviceWatcher>,
+    _linked: linked::LinkWatcher,
+    _mute: Option<linked::MuteWatcher>,
+}
+
+/// Start monitoring a source, which is either the given `target` or the default source.
+fn monitor_source(
+    core: &Core,
+    queues: &Queues,
+    controls: &Arc<Controls>,
+    args: &Args,
+    source: SourceId,
+    target: Option<&str>,
+) -> Result<Monitor> {
+    let capture = Rc::new(create_capture(
+        core,
+        queues.clone(),
+        controls.clone(),
+        args,
+        source,
+        target,
+    )?);
+    let media_class = if args.monitor_sink {
+        "Audio/Sink"
+    } else {
+        "Audio/Source"
+    };
+    let selected: Rc<Cell<Option<u32>>> = Rc::default();
+    let prefer = args.preferred_sources();
+    let preferred = match target {
+        Some(_) => None,
+        None if prefer.is_empty() => None,
+        None => Some(Rc::new(preferred::watch(core, media_class, prefer, {
+            let capture = capture.clone();
+            let args = args.clone();
+            let controls = controls.clone();
+            let selected = selected.clone();
+            move |best| {
+                if selected.get().is_some() {
+                    return;
+                }
+                let (ref stream, _) = *capture;
+                let id = match best {
+                    Some((id, name)) => {
+                        eprintln!("switching to preferred device {name}");
+                        Some(id)
+                    }
+                    None => {
+                        eprintln!("no preferred device present, falling back to the default");
+                        None
+                    }
+                };
+                if let Err(e) = reconnect_capture(stream, &args, &controls, id) {
+                    eprintln!("error: cannot reconnect capture stream: {e}");
+                }
+            }
+        })?)),
+    };
+    // An explicit target is never changed, so only the default source or sink is followed.
+    let default_source = match target {
+        Some(_) => None,
+        None => Some(default_source::watch(core, args.monitor_sink, {
+            let capture = capture.clone();
+            let args = args.clone();
+            let controls = controls.clone();
+            let preferred = preferred.clone();
+            let selected = selected.clone();
+            move || {
+                // A selected or preferred device stays in use regardless of the default.
+                if selected.get().is_some() || preferred.as_ref().and_then(|p| p.best()).is_some() {
+                    return;
+                }
+                eprintln!("default device changed, reconnecting");
+                let (ref stream, _) = *capture;
+                if let Err(e) = reconnect_capture(stream, &args, &controls, None) {
+                    eprintln!("error: cannot reconnect capture stream: {e}");
+                }
+            }
+        })?),
+    };
+    let devices = Rc::new(hotplug::watch(
+        core,
+        media_class,
+        target.map(str::to_owned),
+        args.exclude.clone(),
+        {
+            let capture = capture.clone();
+            let args = args.clone();
+            let controls = controls.clone();
+            let queues = queues.clone();
+            let preferred = preferred.clone();
+            let selected = selected.clone();
+            move |available| {
+                if available {
+                    eprintln!("device appeared, reconnecting");
+                    let (ref stream, _) = *capture;
+                    let id = selected
+                        .get()
+                        .or_else(|| preferred.as_ref().and_then(|p| p.best()));
+                    if let Err(e) = reconnect_capture(stream, &args, &controls, id) {
+                        eprintln!("error: cannot reconnect capture stream: {e}");
+                    }
+                } else {
+                    eprintln!("warning: no device available, waiting for one to appear");
+                    queues.send(Some(source), MicEvent::NoDevice);
+                }
+            }
+        },
+    )?);
+    let on_mute_change = Rc::new({
+        let queues = queues.clone();
+        move |muted: bool| {
+            let event = if muted {
+                MicEvent::Muted
+            } else {
+                MicEvent::Unmuted
+            };
+            queues.send(Some(source), event);
+        }
+    });
+    let linked = linked::watch(
+        core,
+        {
+            let capture = capture.clone();
+            move || capture.0.node_id()
+        },
+        {
+            let capture = capture.clone();
+            let args = args.clone();
+            let controls = controls.clone();
+            let devices = devices.clone();
+            let has_target = target.is_some();
+            move |name| {
+                if has_target || !args.exclude.iter().any(|p| pattern::matches(p, name)) {
+                    return;
+                }
+                let (ref stream, _) = *capture;
+                let result = match devices.first_device() {
+                    Some(id) => {
+                        eprintln!("{name} is excluded, switching to another device");
+                        reconnect_capture(stream, &args, &controls, Some(id))
+                    }
+                    None => {
+                        eprintln!("warning: {name} is excluded, but there is no other device");
+                        stream.disconnect().map_err(Into::into)
+                    }
+                };
+                if let Err(e) = result {
+                    eprintln!("error: cannot reconnect capture stream: {e}");
+                }
+            }
+        },
+        {
+            let on_mute_change = on_mute_change.clone();
+            let has_mute_target = args.mute_target.is_some();
+            move |muted| {
+                if !has_mute_target {
+                    on_mute_change(muted);
+                }
+            }
+        },
+    )?;
+    let mute = match args.mute_target {
+        Some(ref name) => Some(linked::watch_mute(core, name, move |muted| {
+            on_mute_change(muted)
+        })?),
+        None => None,
+    };
+    Ok(Monitor {
+        capture,
+        selected,
+        _default_source: default_source,
+        preferred,
+        _devices: devices,
+        _linked: linked,
+        _mute: mute,
+    })
+}
+
 fn create_capture(
     core: &Core,
-    senders: Vec<mpsc::Sender<MicEvent>>,
-    threshold: f32,
-    hold_time: Duration,
+    queues: Queues,
+    controls: Arc<Controls>,
+    args: &Args,
+    source: SourceId,
+    target: Option<&str>,
 ) -> Result<(Stream, StreamListener<CaptureState>)> {
+    if args.window.is_zero() {
+        anyhow::bail!("--window must not be zero");
+    }
+    #[cfg(not(feature = "spectral"))]
+    if let DetectionMode::Spectral = args.detection_mode {
+        anyhow::bail!("spectral detection requires building with the \"spectral\" feature");
+    }
+
     let state = CaptureState {
-        queues: senders,
-        threshold: 10f32.powf(threshold / 20.),
-        hold_time: hold_time,
-        falloff: Instant::now(),
-        is_on: false,
+        queues,
+        source,
+        controls,
+        adaptive_margin: args.adaptive_margin.map(|m| 10f32.powf(m / 20.)),
+        noise_floor: NoiseFloor::default(),
+        attack_time: args.attack_time,
+        release_time: args.release_time,
+        min_burst_time: args.min_burst_time,
+        min_toggle_interval: args.min_toggle_interval,
+        last_toggle: None,
+        smoothing: args.smoothing,
+        smoothed_level: 0.,
+        classifier: args.classify.then(|| Classifier::new(args.window)),
+        capture: Capture::new(args),
+        decimation: args.decimation,
+        buffer_count: 0,
+        clipping_windows: 0,
+        window_duration: args.window,
+        time: Duration::ZERO,
+        onset: None,
+        falloff: Duration::ZERO,
+        gate: Gate::Closed,
+        conversation: args
+            .conversation
+            .map(|min_speech| Conversation::new(min_speech, args.conversation_gap, args.window)),
+        quiet_level: args.quiet_level.map_or(0., |db| args.linear(db)),
+        loud_level: args.loud_level.map_or(f32::INFINITY, |db| args.linear(db)),
+        loudness: Loudness::Normal,
+        loudness_since: None,
+        peak_decay: 10f32.powf(-args.peak_decay * args.window.as_secs_f32() / 20.),
+        meter_peak: 0.,
+        meter_sent: Duration::ZERO,
     };
 
-    let props = properties! {
+    let mut props = properties! {
         *keys::MEDIA_TYPE => "Audio",
         *keys::MEDIA_CATEGORY => "Capture",
         *keys::MEDIA_ROLE => "Accessibility",
         *keys::NODE_PASSIVE => "in",
     };
+    if let Some(target) = target {
+        props.insert(*keys::TARGET_OBJECT, target);
+    }
+    if args.monitor_sink {
+        props.insert(*keys::STREAM_CAPTURE_SINK, "true");
+    }
+    if let Some(ref latency) = args.latency {
+        props.insert(*keys::NODE_LATENCY, latency.as_str());
+    }
     let stream = Stream::new(&core, "micclick-capture", props)?;
     let listener = stream
         .add_local_listener_with_user_data(state)
+        .param_changed(on_microphone_param_changed)
         .process(on_microphone_frame)
         .state_changed(on_microphone_state_changed)
         .register()?;
+    connect_capture(&stream, StreamFlags::RT_PROCESS, args, None)?;
+    Ok((stream, listener))
+}
+
+/// Connect a capture stream to its target or the default source, offering all supported sample formats.
+///
+/// If requested in `args`, the channel count and sample rate are fixed as well.
+/// A `target` node id overrides the usual target.
+fn connect_capture(
+    stream: &Stream,
+    flags: StreamFlags,
+    args: &Args,
+    target: Option<u32>,
+) -> Result<()> {
     let mut data = [0 as u8; 1024];
     let mut b: spa_pod_builder = unsafe { zeroed() };
     b.data = data.as_mut_ptr() as *mut std::ffi::c_void;
     b.size = data.len() as u32;
     let mut info: spa_audio_info_raw = unsafe { zeroed() };
-    info.format = SPA_AUDIO_FORMAT_F32;
-    let mut params: [&Pod; 1] = unsafe {
-        [Pod::from_raw(spa_format_audio_raw_build(
-            &mut b,
-            SPA_PARAM_EnumFormat,
-            &mut info,
-        ))]
-    };
+    if args.mono {
+        info.channels = 1;
+        info.position[0] = SPA_AUDIO_CHANNEL_MONO;
+    }
+    if let Some(rate) = args.rate {
+        info.rate = rate;
+    }
+    let mut params: Vec<&Pod> = format::FORMATS
+        .iter()
+        .map(|&format| {
+            info.format = format;
+            unsafe {
+                Pod::from_raw(spa_format_audio_raw_build(
+                    &mut b,
+                    SPA_PARAM_EnumFormat,
+                    &mut info,
+                ))
+            }
+        })
+        .collect();
     stream.connect(
         Direction::Input,
-        None,
-        StreamFlags::AUTOCONNECT | StreamFlags::MAP_BUFFERS | StreamFlags::RT_PROCESS,
+        target,
+        StreamFlags::AUTOCONNECT | StreamFlags::MAP_BUFFERS | flags,
         &mut params,
     )?;
-    Ok((stream, listener))
+    Ok(())
+}
+
+/// Reconnect the capture stream, so that it moves to the current default source or sink,
+/// to its target once that reappeared, or to the given `target` node id.
+///
+/// While monitoring is paused, the stream stays inactive.
+fn reconnect_capture(
+    stream: &Stream,
+    args: &Args,
+    controls: &Controls,
+    target: Option<u32>,
+) -> Result<()> {
+    stream.disconnect()?;
+    let mut flags = StreamFlags::RT_PROCESS;
+    if controls.paused() {
+        flags |= StreamFlags::INACTIVE;
+    }
+    connect_capture(stream, flags, args, target)
+}
+
+/// Parse the format that was negotiated for a capture stream.
+///
+/// Returns None if the changed param is not the format, or if the format is
+/// not supported.
+fn negotiated_format(id: u32, param: Option<&Pod>) -> Option<(SampleFormat, AudioInfoRaw)> {
+    let param = param?;
+    if id != SPA_PARAM_Format {
+        return None;
+    }
+    let mut info = AudioInfoRaw::new();
+    if let Err(e) = info.parse(param) {
+        eprintln!("error: cannot parse capture format: {e}");
+        return None;
+    }
+    let Some(format) = SampleFormat::from_raw(info.as_raw().format) else {
+        eprintln!("error: unsupported capture format: {:?}", info.format());
+        return None;
+    };
+    Some((format, info))
+}
+
+fn on_microphone_param_changed(
+    _stream: &StreamRef,
+    state: &mut CaptureState,
+    id: u32,
+    param: Option<&Pod>,
+) {
+    let Some((format, info)) = negotiated_format(id, param) else {
+        return;
+    };
+    state.capture.set_format(format, &info);
 }
 
 fn on_microphone_frame(stream: &StreamRef, state: &mut CaptureState) {
@@ -135,46 +1464,172 @@ fn on_microphone_frame(stream: &StreamRef, state: &mut CaptureState) {
         println!("error: capture stream is out of buffers");
         return;
     };
-    let datas = buffer.datas_mut();
-    assert_eq!(datas.len(), 1, "expected exactly one data buffer");
-
-    let n_samples = datas[0].chunk().size() / size_of::<f32>() as u32;
-    if n_samples == 0 {
+    state.buffer_count = state.buffer_count.wrapping_add(1);
+    if state.buffer_count % state.decimation != 0 {
+        // Skip the buffer, but keep the clock running so that timings stay correct.
+        // A partial window is kept, so that it can still fill up if buffers are
+        // shorter than the analysis window.
+        if let Some(duration) = state.capture.buffer_duration(buffer.datas_mut()) {
+            state.time += duration;
+        }
         return;
     }
-    let Some(samples) = datas[0].data() else {
+
+    state.capture.read(buffer.datas_mut());
+    while state.capture.window().is_some() {
+        analyze_window(state);
+        state.capture.advance();
+    }
+}
+
+/// Run the detection on the next full analysis window.
+fn analyze_window(state: &mut CaptureState) {
+    state.time += state.window_duration;
+
+    if state
+        .capture
+        .window()
+        .is_some_and(|window| window.iter().any(|s| s.abs() >= CLIPPING_LEVEL))
+    {
+        state.clipping_windows = state.clipping_windows.saturating_add(1);
+        if state.clipping_windows == CLIPPING_WINDOWS {
+            state.queues.send(Some(state.source), MicEvent::Clipping);
+        }
+    } else {
+        state.clipping_windows = 0;
+    }
+
+    let window = state.capture.filter_window();
+    let class = match state.classifier {
+        Some(ref mut classifier) => classifier.classify(window),
+        None => SoundClass::Speech,
+    };
+    let Some(measurement) = state.capture.measure() else {
         return;
     };
-    let (head, samples, tail) = unsafe { samples.align_to::<f32>() };
-    assert!(head.is_empty(), "misaligned data buffer");
-    assert!(tail.is_empty(), "misaligned data buffer");
+    let mut level = measurement.level;
+    let ungated = level;
 
-    let mut max = 0f32;
-    for n in 0..n_samples {
-        max = samples[n as usize].abs().max(max);
+    let (mut threshold_on, mut threshold_off) = state.controls.thresholds();
+    if let Some(margin) = state.adaptive_margin {
+        let adaptive = state.noise_floor.update(level) * margin;
+        threshold_off = threshold_off.max(adaptive * threshold_off / threshold_on);
+        threshold_on = threshold_on.max(adaptive);
     }
-    let max = max;
 
-    let now = Instant::now();
-    if max > state.threshold {
-        state.falloff = now + state.hold_time;
+    if !measurement.speech || state.controls.input_suppressed() {
+        level = 0.;
+    }
+    // Other sounds may not turn the microphone on, but do not interrupt speech either.
+    if state.gate == Gate::Closed && class != SoundClass::Speech {
+        level = 0.;
     }
 
-    let event: MicEvent;
-    match (state.is_on, now <= state.falloff) {
-        (false, true) => {
-            state.is_on = true;
-            event = MicEvent::Active;
+    if let Some(smoothing) = state.smoothing {
+        let alpha = 1. - (-state.window_duration.as_secs_f32() / smoothing.as_secs_f32()).exp();
+        state.smoothed_level += alpha * (level - state.smoothed_level);
+        level = state.smoothed_level;
+    }
+
+    state.meter_peak = level.max(state.meter_peak * state.peak_decay);
+    if state.time - state.meter_sent >= METER_INTERVAL {
+        state.meter_sent = state.time;
+        let meter = Meter {
+            level,
+            peak: state.meter_peak,
+            ungated,
+        };
+        state
+            .queues
+            .send(Some(state.source), MicEvent::Level(meter));
+    }
+
+    let threshold = if state.gate != Gate::Closed {
+        threshold_off
+    } else {
+        threshold_on
+    };
+    let now = state.time;
+    if level > threshold {
+        let onset = *state.onset.get_or_insert(now);
+        let min_time = if state.gate == Gate::Closed {
+            state.attack_time.max(state.min_burst_time)
+        } else {
+            state.min_burst_time
+        };
+        if now - onset >= min_time {
+            state.falloff = now + state.controls.hold_time();
         }
-        (true, false) => {
-            state.is_on = false;
-            event = MicEvent::Inactive;
+    } else {
+        state.onset = None;
+    }
+
+    if state.gate == Gate::Open && level > threshold {
+        let loudness = if level < state.quiet_level {
+            Loudness::Quiet
+        } else if level > state.loud_level {
+            Loudness::Loud
+        } else {
+            Loudness::Normal
+        };
+        if loudness == state.loudness {
+            state.loudness_since = None;
+        } else if now - *state.loudness_since.get_or_insert(now) >= LOUDNESS_SETTLE_TIME {
+            state.loudness = loudness;
+            state.loudness_since = None;
+            state
+                .queues
+                .send(Some(state.source), MicEvent::Loudness(loudness));
+        }
+    }
+
+    let gate = if now <= state.falloff {
+        Gate::Open
+    } else if state.gate != Gate::Closed && now <= state.falloff + state.release_time {
+        Gate::Releasing
+    } else {
+        Gate::Closed
+    };
+    if let Some(ref mut conversation) = state.conversation {
+        if let Some(active) = conversation.update(now, gate == Gate::Open) {
+            let event = if active {
+                MicEvent::Active
+            } else {
+                MicEvent::Inactive
+            };
+            state.queues.send(Some(state.source), event);
         }
-        _ => return,
     }
-    for q in state.queues.iter() {
-        q.send(event).expect("cannot send: channel broken");
+
+    if gate == state.gate {
+        return;
+    }
+    // Releasing is part of the "on" state, so only opening and closing count as toggles.
+    if state.gate == Gate::Closed || gate == Gate::Closed {
+        if state
+            .last_toggle
+            .is_some_and(|last| now - last < state.min_toggle_interval)
+        {
+            return;
+        }
+        state.last_toggle = Some(now);
     }
+    state.gate = gate;
+    let event = match gate {
+        Gate::Open => MicEvent::Active,
+        Gate::Releasing => MicEvent::Releasing,
+        Gate::Closed => {
+            state.onset = None;
+            state.loudness = Loudness::Normal;
+            state.loudness_since = None;
+            MicEvent::Inactive
+        }
+    };
+    // In conversation mode, only the start and end of conversations are reported.
+    if state.conversation.is_some() {
+        return;
+    }
+    state.queues.send(Some(state.source), event);
 }
 
 fn on_microphone_state_changed(
@@ -184,106 +1639,410 @@ fn on_microphone_state_changed(
     new: StreamState,
 ) {
     let event = match (old, new) {
-        (_, StreamState::Error(e)) => panic!("capture stream entered error state: {e:?}"),
+        (_, StreamState::Error(e)) => {
+            eprintln!("error: capture stream entered error state: {e}");
+            MicEvent::Error
+        }
         (StreamState::Paused, StreamState::Streaming) => MicEvent::Inactive,
-        (StreamState::Streaming, StreamState::Paused) => MicEvent::Suspended,
+        (StreamState::Streaming, StreamState::Paused) => {
+            if let Some(ref mut conversation) = state.conversation {
+                conversation.reset();
+            }
+            MicEvent::Suspended
+        }
         _ => return,
     };
-    for q in state.queues.iter() {
-        q.send(event).expect("cannot send: channel broken");
+    state.queues.send(Some(state.source), event);
+}
+
+/// Limits the on and off sounds to one per --sound-cooldown, and holds back the
+/// last one until the cooldown expires, so that the final state is still heard.
+struct Cooldown<'a> {
+    time: Duration,
+    /// When the last on or off sound was played, and whether it was the on sound.
+    last: Option<(Instant, bool)>,
+    /// The sound that is held back, with when it is due and whether it is the on sound.
+    deferred: Option<(Instant, bool, &'a [Arc<Sound>])>,
+}
+
+impl<'a> Cooldown<'a> {
+    /// Check whether the on or off sound can be played now. If not, it is
+    /// deferred until the cooldown expires, unless the last sound that was
+    /// played already matches it.
+    fn allow(&mut self, on: bool, sound: &'a [Arc<Sound>]) -> bool {
+        let now = Instant::now();
+        match self.last {
+            Some((last, last_on)) if now < last + self.time => {
+                self.deferred = (last_on != on).then_some((last + self.time, on, sound));
+                false
+            }
+            _ => {
+                self.last = Some((now, on));
+                self.deferred = None;
+                true
+            }
+        }
+    }
+
+    /// When the deferred sound is due, if there is one.
+    fn next_due(&self) -> Option<Instant> {
+        self.deferred.map(|(due, _, _)| due)
+    }
+
+    /// Take the deferred sound and whether it is the on sound, once it is due.
+    fn due(&mut self, now: Instant) -> Option<(bool, &'a [Arc<Sound>])> {
+        let (due, on, sound) = self.deferred?;
+        if due > now {
+            return None;
+        }
+        self.deferred = None;
+        self.last = Some((now, on));
+        Some((on, sound))
     }
 }
 
 fn clicker_thread_main(
-    eventreceiver: mpsc::Receiver<MicEvent>,
-    on_sound: Option<String>,
-    off_sound: Option<String>,
+    eventreceiver: mpsc::Receiver<Event>,
+    player: pipewire::channel::Sender<Arc<Sound>>,
+    controls: Arc<Controls>,
+    args: &Args,
+    sources: Vec<Option<config::SourceSounds>>,
 ) {
-    let mut on_sound = match on_sound {
-        Some(path) => load_sound(&path),
-        None => None,
+    let load = |path: &Option<String>| path.as_deref().map(load_sounds).unwrap_or_default();
+    let tone = |tone: Option<Tone>| tone.map(|t| Arc::new(Sound::tone(t))).into_iter().collect();
+    let repeat = |sounds: Vec<Arc<Sound>>, count: u32, gap: Duration| -> Vec<Arc<Sound>> {
+        match count {
+            1 => sounds,
+            _ => sounds
+                .iter()
+                .map(|sound| Arc::new(sound.repeated(count, gap)))
+                .collect(),
+        }
     };
-    let mut off_sound = match off_sound {
-        Some(path) => load_sound(&path),
-        None => None,
+    let default_click = |on: bool| {
+        if args.no_default_sounds {
+            vec![]
+        } else {
+            vec![Arc::new(Sound::default_click(on))]
+        }
+    };
+    let mut on_sound = load(&args.on_sound);
+    if on_sound.is_empty() {
+        on_sound = tone(args.on_tone);
+    }
+    if on_sound.is_empty() && args.on_sound.is_none() {
+        on_sound = default_click(true);
+    }
+    let on_sound = repeat(on_sound, args.on_sound_repeat, args.on_sound_gap);
+    let mut off_sound = load(&args.off_sound);
+    if off_sound.is_empty() {
+        off_sound = tone(args.off_tone);
+    }
+    if off_sound.is_empty() && args.off_sound.is_none() {
+        off_sound = default_click(false);
+    }
+    let suspended_sound = load(&args.suspended_sound);
+    let error_sound = load(&args.error_sound);
+    let clipping_sound = load(&args.clipping_sound);
+    let loud_sound = load(&args.loud_sound);
+    let recording_sound = load(&args.recording_sound);
+    let headset_sound = load(&args.headset_sound);
+    let camera_on_sound = load(&args.camera_on_sound);
+    let camera_off_sound = load(&args.camera_off_sound);
+    let reminder_sound = load(&args.reminder_sound);
+    let muted_sound = repeat(
+        load(&args.muted_sound),
+        args.muted_sound_repeat,
+        args.muted_sound_gap,
+    );
+    // The on and off sounds of the sources that have their own.
+    let source_sounds: Vec<Option<(Vec<Arc<Sound>>, Vec<Arc<Sound>>)>> = sources
+        .iter()
+        .map(|sounds| {
+            let sounds = sounds.as_ref()?;
+            let on = load(&sounds.on_sound);
+            let off = load(&sounds.off_sound);
+            Some((
+                if on.is_empty() { on_sound.clone() } else { on },
+                if off.is_empty() {
+                    off_sound.clone()
+                } else {
+                    off
+                },
+            ))
+        })
+        .collect();
+    let own_sounds = |source: Option<SourceId>| source_sounds.get(source?)?.as_ref();
+    // Ignore the input while a sound plays, which may be repeated, and for
+    // --feedback-suppression after it.
+    let suppress_feedback = |sound: &Arc<Sound>| {
+        if !args.feedback_suppression.is_zero() {
+            controls.suppress_input(sound.duration() + args.feedback_suppression);
+        }
+    };
+    let play = |sounds: &[Arc<Sound>]| {
+        // Pick one of the sounds at random, in case a directory was given.
+        if let Some(sound) = random_choice(sounds) {
+            if controls.sounds() && !controls.silenced() && !controls.paused() {
+                suppress_feedback(sound);
+                let _ = player.send(sound.clone());
+            }
+        }
     };
 
-    let mut is_active = false;
+    let mut haptic = args
+        .haptic_device
+        .as_deref()
+        .and_then(|path| match Haptic::open(path) {
+            Ok(haptic) => Some(haptic),
+            Err(e) => {
+                eprintln!("error: {e:#}");
+                None
+            }
+        });
+    // Give feedback other than the sounds when the microphone turns on or off.
+    let mut notify = |on: bool| {
+        if controls.paused() {
+            return;
+        }
+        if args.announce && controls.sounds() && !controls.silenced() {
+            speech::say(if on {
+                "microphone live"
+            } else {
+                "microphone off"
+            });
+        }
+        if let Some(ref mut haptic) = haptic {
+            haptic.pulse(on);
+        }
+    };
 
+    // The sources that are on. Among those without their own sounds, the on
+    // sound is played when the first one turns on, and the off sound when the
+    // last one turns off.
+    let mut active: BTreeSet<Option<SourceId>> = BTreeSet::new();
+    let mut muted: BTreeSet<Option<SourceId>> = BTreeSet::new();
+    // The number of applications that use a camera.
+    let mut cameras = 0usize;
+    let mut cooldown = Cooldown {
+        time: args.sound_cooldown,
+        last: None,
+        deferred: None,
+    };
+    // When the next reminder is due, if one is.
+    let mut reminder_due: Option<Instant> = None;
+    // Off sounds that wait for --off-sound-delay, by the source that turned
+    // off, or by None for the sources without their own sounds.
+    let mut pending_off: HashMap<Option<SourceId>, (Instant, &Vec<Arc<Sound>>)> = HashMap::new();
     loop {
-        match eventreceiver.recv() {
-            Ok(MicEvent::Active) => {
-                if !is_active {
-                    if let Some(ref mut sound) = on_sound {
-                        sound.play();
+        if active.is_empty() || reminder_sound.is_empty() {
+            reminder_due = None;
+        } else if reminder_due.is_none() {
+            reminder_due = Some(Instant::now() + args.reminder_after.max(args.reminder_interval));
+        }
+        let next_due = pending_off
+            .values()
+            .map(|&(due, _)| due)
+            .chain(reminder_due)
+            .chain(cooldown.next_due())
+            .min();
+        let received = match next_due {
+            Some(due) => eventreceiver.recv_timeout(due.saturating_duration_since(Instant::now())),
+            None => eventreceiver.recv().map_err(mpsc::RecvTimeoutError::from),
+        };
+        let Event { source, event } = match received {
+            Ok(event) => event,
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                let now = Instant::now();
+                if reminder_due.is_some_and(|due| due <= now) {
+                    reminder_due = reminder_due.map(|due| due + args.reminder_interval);
+                    play(&reminder_sound);
+                }
+                let mut due_off: Vec<&Vec<Arc<Sound>>> = Vec::new();
+                pending_off.retain(|_, &mut (due, sound)| {
+                    if due <= now {
+                        due_off.push(sound);
+                    }
+                    due > now
+                });
+                for sound in due_off {
+                    if cooldown.allow(false, sound) {
+                        notify(false);
+                        play(sound);
                     }
                 }
-                is_active = true;
+                if let Some((on, sound)) = cooldown.due(now) {
+                    notify(on);
+                    play(sound);
+                }
+                continue;
             }
-            Ok(MicEvent::Inactive | MicEvent::Suspended) => {
-                if is_active {
-                    if let Some(ref mut sound) = off_sound {
-                        sound.play();
-                    }
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        };
+        // The key of the source in `pending_off`.
+        let off_key = own_sounds(source).and(source);
+        let sound = match event {
+            MicEvent::Active => {
+                if muted.contains(&source) {
+                    // Talking while muted deserves a warning instead of the
+                    // on sound, regardless of any other source.
+                    play(&muted_sound);
+                    continue;
+                }
+                let was_active = active.iter().any(|&s| own_sounds(s).is_none());
+                if !active.insert(source) {
+                    continue;
+                }
+                if pending_off.remove(&off_key).is_some() {
+                    // The off sound was not played yet, so the on sound is not needed either.
+                    continue;
+                }
+                let sound = match own_sounds(source) {
+                    Some((on, _)) => on,
+                    None if was_active => continue,
+                    None => &on_sound,
+                };
+                if !cooldown.allow(true, sound) {
+                    continue;
+                }
+                notify(true);
+                sound
+            }
+            MicEvent::Releasing => {
+                // The off sound is only played once the release phase is over.
+                continue;
+            }
+            MicEvent::Suspended if !suspended_sound.is_empty() => {
+                // Like the off sound, only for a source that was on.
+                if !active.remove(&source) {
+                    continue;
+                }
+                &suspended_sound
+            }
+            MicEvent::NoDevice | MicEvent::Error if !error_sound.is_empty() => {
+                active.remove(&source);
+                &error_sound
+            }
+            MicEvent::Inactive | MicEvent::Suspended | MicEvent::NoDevice | MicEvent::Error => {
+                if !active.remove(&source) {
+                    continue;
+                }
+                let sound = match own_sounds(source) {
+                    Some((_, off)) => off,
+                    None if active.iter().any(|&s| own_sounds(s).is_none()) => continue,
+                    None => &off_sound,
+                };
+                if !args.off_sound_delay.is_zero() {
+                    pending_off.insert(off_key, (Instant::now() + args.off_sound_delay, sound));
+                    continue;
                 }
-                is_active = false;
+                if !cooldown.allow(false, sound) {
+                    continue;
+                }
+                notify(false);
+                sound
+            }
+            MicEvent::Clipping => &clipping_sound,
+            MicEvent::Loudness(Loudness::Loud) => &loud_sound,
+            MicEvent::Loudness(_) | MicEvent::Level(_) => continue,
+            MicEvent::RecordingStarted(_) => &recording_sound,
+            MicEvent::HeadsetMicOn(_) => &headset_sound,
+            MicEvent::CameraStarted(_) => {
+                cameras += 1;
+                if cameras > 1 {
+                    continue;
+                }
+                &camera_on_sound
+            }
+            MicEvent::CameraStopped(_) => {
+                cameras = cameras.saturating_sub(1);
+                if cameras > 0 {
+                    continue;
+                }
+                &camera_off_sound
+            }
+            MicEvent::TestSound(on) => {
+                // Play the sound even if sounds are disabled, as it was asked for explicitly.
+                if let Some(sound) = random_choice(if on { &on_sound } else { &off_sound }) {
+                    suppress_feedback(sound);
+                    let _ = player.send(sound.clone());
+                }
+                continue;
+            }
+            MicEvent::Muted => {
+                muted.insert(source);
+                continue;
+            }
+            MicEvent::Unmuted => {
+                muted.remove(&source);
+                continue;
+            }
+            MicEvent::RecordingStopped(_)
+            | MicEvent::HeadsetMicOff(_)
+            | MicEvent::Paused(_)
+            | MicEvent::Silenced(_)
+            | MicEvent::SourcesChanged => continue,
+        };
+        play(sound);
+    }
+}
+
+fn meter_thread_main(eventreceiver: mpsc::Receiver<Event>, labels: Vec<String>, db_offset: f32) {
+    let db = |level: f32| 20. * level.max(f32::MIN_POSITIVE).log10() + db_offset;
+    for event in eventreceiver {
+        if let (Some(source), MicEvent::Level(meter)) = (event.source, event.event) {
+            if labels.len() > 1 {
+                print!("{}: ", labels[source]);
             }
-            Err(_) => break,
+            println!(
+                "{:6.1} dB (peak {:6.1} dB)",
+                db(meter.level),
+                db(meter.peak)
+            );
         }
     }
 }
 
-fn load_sound(path: &str) -> Option<Sound> {
-    match Sound::new(path) {
-        Ok(sound) => Some(sound),
+fn load_sounds(path: &str) -> Vec<Arc<Sound>> {
+    match Sound::load_all(path) {
+        Ok(sounds) => sounds.into_iter().map(Arc::new).collect(),
         Err(e) => {
             eprintln!("failed to load sound effect from {path:?}: {e}");
-            None
+            vec![]
         }
     }
 }
 
-static mut INDICATOR_MENU: *mut gtk::Menu = std::ptr::null_mut();
-static mut INDICATOR: *mut AppIndicator = std::ptr::null_mut();
-static INDICATOR_INIT: std::sync::Once = std::sync::Once::new();
-
-fn tray_thread_main(eventreceiver: mpsc::Receiver<MicEvent>) {
-    gtk::init().expect("gtk::init() failed");
+fn random_choice<T>(items: &[T]) -> Option<&T> {
+    match items.len() {
+        0 => None,
+        1 => items.first(),
+        n => items.get(glib::random_int_range(0, n as i32) as usize),
+    }
+}
 
-    gtk::glib::source::timeout_add(Duration::from_millis(40), move || {
-        INDICATOR_INIT.call_once(|| unsafe {
-            INDICATOR = Box::into_raw(Box::new(AppIndicator::new("pw-micclick", "")));
-            (*INDICATOR).set_status(AppIndicatorStatus::Passive);
-            (*INDICATOR).set_icon_full("microphone-sensitivity-muted-symbolic", "icon");
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-            INDICATOR_MENU = Box::into_raw(Box::new(gtk::Menu::new()));
-            (*INDICATOR).set_menu(&mut *INDICATOR_MENU);
-            (*INDICATOR_MENU).show_all();
-        });
+    #[test]
+    fn parse_volume_in_db_or_linear() {
+        assert_eq!(parse_volume("0.5").unwrap(), 0.5);
+        assert_eq!(parse_volume("0dB").unwrap(), 1.);
+        assert!((parse_volume("-6 dB").unwrap() - 0.501).abs() < 1e-3);
+        assert!((parse_volume("-20db").unwrap() - 0.1).abs() < 1e-6);
+        for invalid in ["1.5", "-0.1", "6dB", "NaNdB", "loud"] {
+            assert!(parse_volume(invalid).is_err(), "{invalid}");
+        }
+    }
 
-        let indicator = unsafe { &mut *INDICATOR };
-        match eventreceiver.try_recv() {
-            Ok(MicEvent::Active) => {
-                indicator.set_icon_full("microphone-sensitivity-high-symbolic", "icon");
-                indicator.set_status(AppIndicatorStatus::Active);
-                gtk::glib::ControlFlow::Continue
-            }
-            Ok(MicEvent::Inactive) => {
-                indicator.set_icon_full("microphone-sensitivity-low-symbolic", "icon");
-                indicator.set_status(AppIndicatorStatus::Active);
-                gtk::glib::ControlFlow::Continue
-            }
-            Ok(MicEvent::Suspended) => {
-                indicator.set_icon_full("microphone-sensitivity-muted-symbolic", "icon");
-                indicator.set_status(AppIndicatorStatus::Passive);
-                gtk::glib::ControlFlow::Continue
-            }
-            Err(mpsc::TryRecvError::Empty) => gtk::glib::ControlFlow::Continue,
-            Err(mpsc::TryRecvError::Disconnected) => {
-                gtk::main_quit();
-                gtk::glib::ControlFlow::Break
-            }
+    #[test]
+    fn parse_millis_with_or_without_unit() {
+        assert_eq!(parse_millis("200").unwrap(), Duration::from_millis(200));
+        assert_eq!(parse_millis("200ms").unwrap(), Duration::from_millis(200));
+        assert_eq!(parse_millis("1.5s").unwrap(), Duration::from_millis(1500));
+        for invalid in ["-1", "1min", "ms", "NaN"] {
+            assert!(parse_millis(invalid).is_err(), "{invalid}");
         }
-    });
-    gtk::main();
+    }
 }
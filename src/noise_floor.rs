@@ -0,0 +1,31 @@
+//! Estimation of the ambient noise floor of the captured signal.
+
+/// Factor by which the estimate rises for each buffer that is louder than it.
+const RISE: f32 = 1.0002;
+/// Lowest possible estimate, to avoid getting stuck after digital silence.
+const MIN: f32 = 1e-5;
+
+/// Tracks the noise floor by following quieter buffers immediately and
+/// louder ones only very slowly.
+#[derive(Debug)]
+pub struct NoiseFloor {
+    level: f32,
+}
+
+impl Default for NoiseFloor {
+    fn default() -> Self {
+        Self { level: MIN }
+    }
+}
+
+impl NoiseFloor {
+    /// Update the estimate with the level of another buffer and return the new estimate.
+    pub fn update(&mut self, level: f32) -> f32 {
+        if level < self.level {
+            self.level = level.max(MIN);
+        } else {
+            self.level = (self.level * RISE).min(level);
+        }
+        self.level
+    }
+}
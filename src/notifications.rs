@@ -0,0 +1,237 @@
+//! Desktop notifications about events that are easy to miss in the tray,
+//! sent to the freedesktop notification service over D-Bus, with buttons
+//! that pause monitoring.
+
+use crate::{Event, MicEvent, Request, SourceId};
+use anyhow::{Context, Result};
+use gio::{BusType, DBusCallFlags, DBusConnection, DBusSignalFlags};
+use glib::ToVariant;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+const NOTIFICATIONS: &str = "org.freedesktop.Notifications";
+const NOTIFICATIONS_PATH: &str = "/org/freedesktop/Notifications";
+
+/// How long "Snooze" pauses monitoring for.
+const SNOOZE_TIME: Duration = Duration::from_secs(10 * 60);
+
+/// The actions of the notifications that something is using the microphone,
+/// as pairs of key and label.
+const PAUSE_ACTIONS: [&str; 4] = ["pause", "Pause monitoring", "snooze", "Snooze 10 min"];
+
+/// The events that can be notified about.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, clap::ValueEnum)]
+pub enum NotifyEvent {
+    /// No device is left to capture from, or the capture stream failed.
+    DeviceLost,
+    /// There is input on a source while it is muted.
+    Muted,
+    /// Another application started capturing audio.
+    Recording,
+}
+
+/// Show a notification for each of the given `events` that is received,
+/// until the event channel is closed.
+pub fn thread_main(
+    eventreceiver: mpsc::Receiver<Event>,
+    labels: Vec<String>,
+    events: Vec<NotifyEvent>,
+    request_sender: pipewire::channel::Sender<Request>,
+) {
+    let bus = match gio::bus_get_sync(BusType::Session, None::<&gio::Cancellable>) {
+        Ok(bus) => bus,
+        Err(e) => {
+            eprintln!("error: cannot connect to the session bus for notifications: {e}");
+            return;
+        }
+    };
+    // The notifications that are open, whose actions are ours to handle.
+    let open: Arc<Mutex<HashSet<u32>>> = Arc::default();
+    thread::spawn({
+        let bus = bus.clone();
+        let open = open.clone();
+        move || {
+            if let Err(e) = handle_actions(&bus, open, request_sender) {
+                eprintln!("error: cannot handle notification actions: {e:#}");
+            }
+        }
+    });
+    // The sources that are muted.
+    let mut muted: BTreeSet<Option<SourceId>> = BTreeSet::new();
+    // The last notification of each kind, which the next one replaces.
+    let mut shown: HashMap<NotifyEvent, u32> = HashMap::new();
+    // Name the source only if there are several.
+    let source_name = |source: Option<SourceId>| match source {
+        Some(source) if labels.len() > 1 => format!("{} ", labels[source]),
+        _ => String::new(),
+    };
+    for Event { source, event } in eventreceiver {
+        let (kind, icon, summary, body, actions) = match event {
+            MicEvent::Muted => {
+                muted.insert(source);
+                continue;
+            }
+            MicEvent::Unmuted => {
+                muted.remove(&source);
+                continue;
+            }
+            MicEvent::Active if muted.contains(&source) => (
+                NotifyEvent::Muted,
+                "microphone-sensitivity-muted-symbolic",
+                "Talking while muted".to_owned(),
+                format!(
+                    "The {}microphone is muted, so nobody hears you.",
+                    source_name(source)
+                ),
+                &PAUSE_ACTIONS[..],
+            ),
+            MicEvent::NoDevice => (
+                NotifyEvent::DeviceLost,
+                "microphone-disabled-symbolic",
+                "No microphone".to_owned(),
+                format!(
+                    "There is no {}device left to capture from, waiting for one to appear.",
+                    source_name(source)
+                ),
+                &[][..],
+            ),
+            MicEvent::Error => (
+                NotifyEvent::DeviceLost,
+                "dialog-error-symbolic",
+                "Monitoring failed".to_owned(),
+                format!("The {}capture stream failed.", source_name(source)),
+                &[][..],
+            ),
+            MicEvent::RecordingStarted(app) => (
+                NotifyEvent::Recording,
+                "audio-input-microphone-symbolic",
+                format!("{app} is using the microphone"),
+                String::new(),
+                &PAUSE_ACTIONS[..],
+            ),
+            _ => continue,
+        };
+        if !events.contains(&kind) {
+            continue;
+        }
+        let replaces = shown.get(&kind).copied().unwrap_or(0);
+        match notify(&bus, replaces, icon, &summary, &body, actions) {
+            Ok(id) => {
+                shown.insert(kind, id);
+                open.lock().unwrap().insert(id);
+            }
+            Err(e) => eprintln!("error: cannot show a notification: {e:#}"),
+        }
+    }
+}
+
+/// Show a notification with the given actions, replacing the one with the id
+/// `replaces` unless that is 0, and return its id.
+fn notify(
+    bus: &DBusConnection,
+    replaces: u32,
+    icon: &str,
+    summary: &str,
+    body: &str,
+    actions: &[&str],
+) -> Result<u32> {
+    let actions = actions.to_vec();
+    let hints: HashMap<String, glib::Variant> = HashMap::new();
+    let parameters = (
+        "pw-micclick",
+        replaces,
+        icon,
+        summary,
+        body,
+        actions,
+        hints,
+        -1i32,
+    )
+        .to_variant();
+    let reply = bus.call_sync(
+        Some(NOTIFICATIONS),
+        NOTIFICATIONS_PATH,
+        NOTIFICATIONS,
+        "Notify",
+        Some(&parameters),
+        None,
+        DBusCallFlags::NONE,
+        -1,
+        None::<&gio::Cancellable>,
+    )?;
+    reply
+        .child_value(0)
+        .get::<u32>()
+        .context("unexpected reply from the notification service")
+}
+
+/// Run a main loop that handles the actions that the user invokes on the
+/// `open` notifications, and forgets the notifications once they are closed.
+fn handle_actions(
+    bus: &DBusConnection,
+    open: Arc<Mutex<HashSet<u32>>>,
+    request_sender: pipewire::channel::Sender<Request>,
+) -> Result<()> {
+    let context = glib::MainContext::new();
+    context
+        .with_thread_default(|| {
+            let _actions = bus.signal_subscribe(
+                Some(NOTIFICATIONS),
+                Some(NOTIFICATIONS),
+                Some("ActionInvoked"),
+                Some(NOTIFICATIONS_PATH),
+                None,
+                DBusSignalFlags::NONE,
+                {
+                    let open = open.clone();
+                    let context = context.clone();
+                    move |_, _, _, _, _, parameters| {
+                        let Some((id, action)) = parameters.get::<(u32, String)>() else {
+                            return;
+                        };
+                        if !open.lock().unwrap().contains(&id) {
+                            return;
+                        }
+                        match action.as_str() {
+                            "pause" => {
+                                let _ = request_sender.send(Request::Pause(true));
+                            }
+                            "snooze" => {
+                                let _ = request_sender.send(Request::Pause(true));
+                                let request_sender = request_sender.clone();
+                                // The default main context belongs to the tray, if anyone.
+                                glib::timeout_source_new(
+                                    SNOOZE_TIME,
+                                    None,
+                                    glib::Priority::DEFAULT,
+                                    move || {
+                                        let _ = request_sender.send(Request::Pause(false));
+                                        glib::ControlFlow::Break
+                                    },
+                                )
+                                .attach(Some(&context));
+                            }
+                            _ => {}
+                        }
+                    }
+                },
+            );
+            let _closed = bus.signal_subscribe(
+                Some(NOTIFICATIONS),
+                Some(NOTIFICATIONS),
+                Some("NotificationClosed"),
+                Some(NOTIFICATIONS_PATH),
+                None,
+                DBusSignalFlags::NONE,
+                move |_, _, _, _, _, parameters| {
+                    if let Some((id, _reason)) = parameters.get::<(u32, u32)>() {
+                        open.lock().unwrap().remove(&id);
+                    }
+                },
+            );
+            glib::MainLoop::new(Some(&context), false).run();
+        })
+        .context("cannot use the main context")
+}
@@ -0,0 +1,115 @@
+//! Small windows that show the tray icon on the screen, for desktops without a
+//! tray: the wlr-layer-shell overlay in a corner of the screen, and the mini
+//! window that stays above other windows.
+
+#[cfg(feature = "layer-shell")]
+use crate::tray::Corner;
+use crate::tray::TrayState;
+use gtk::prelude::*;
+#[cfg(feature = "layer-shell")]
+use gtk_layer_shell::{Edge, KeyboardMode, Layer, LayerShell};
+
+/// The distance between the overlay and the edges of the screen, in pixels.
+#[cfg(feature = "layer-shell")]
+const MARGIN: i32 = 16;
+
+/// A translucent dark background, on which symbolic icons are drawn in white.
+const STYLE: &[u8] =
+    b"window { background-color: rgba(0, 0, 0, 0.6); border-radius: 8px; color: white; }";
+
+pub struct Overlay {
+    window: gtk::Window,
+    image: gtk::Image,
+    /// Whether to show the window while the microphone is off, too.
+    always: bool,
+}
+
+impl Overlay {
+    /// Create the hidden overlay in the given corner, which is only shown
+    /// while the microphone is on, or None if the compositor does not support
+    /// wlr-layer-shell.
+    #[cfg(feature = "layer-shell")]
+    pub fn layer(corner: Corner) -> Option<Self> {
+        if !gtk_layer_shell::is_supported() {
+            eprintln!("error: --overlay needs a compositor with wlr-layer-shell");
+            return None;
+        }
+        let overlay = Self::new(false);
+        let window = &overlay.window;
+        window.init_layer_shell();
+        window.set_layer(Layer::Overlay);
+        window.set_namespace("pw-micclick");
+        window.set_keyboard_mode(KeyboardMode::None);
+        let (vertical, horizontal) = match corner {
+            Corner::TopLeft => (Edge::Top, Edge::Left),
+            Corner::TopRight => (Edge::Top, Edge::Right),
+            Corner::BottomLeft => (Edge::Bottom, Edge::Left),
+            Corner::BottomRight => (Edge::Bottom, Edge::Right),
+        };
+        for edge in [vertical, horizontal] {
+            window.set_anchor(edge, true);
+            window.set_layer_shell_margin(edge, MARGIN);
+        }
+        // Let clicks through to whatever is below the overlay.
+        window.input_shape_combine_region(Some(&gtk::cairo::Region::create()));
+        Some(overlay)
+    }
+
+    /// Create the mini window for --window-indicator, which always shows the
+    /// icon, stays above other windows, and can be moved by dragging it.
+    pub fn window() -> Self {
+        let overlay = Self::new(true);
+        let window = &overlay.window;
+        window.set_title("pw-micclick");
+        window.set_decorated(false);
+        window.set_keep_above(true);
+        window.set_skip_taskbar_hint(true);
+        window.set_skip_pager_hint(true);
+        window.set_accept_focus(false);
+        window.set_type_hint(gtk::gdk::WindowTypeHint::Utility);
+        window.add_events(gtk::gdk::EventMask::BUTTON_PRESS_MASK);
+        window.connect_button_press_event(|window, event| {
+            if event.button() == 1 {
+                let (x, y) = event.root();
+                window.begin_move_drag(1, x as i32, y as i32, event.time());
+            }
+            glib::Propagation::Stop
+        });
+        overlay
+    }
+
+    fn new(always: bool) -> Self {
+        let window = gtk::Window::new(gtk::WindowType::Toplevel);
+        if let Some(visual) = gtk::gdk::Screen::default().and_then(|screen| screen.rgba_visual()) {
+            window.set_visual(Some(&visual));
+        }
+        let provider = gtk::CssProvider::new();
+        provider
+            .load_from_data(STYLE)
+            .expect("invalid overlay style");
+        window
+            .style_context()
+            .add_provider(&provider, gtk::STYLE_PROVIDER_PRIORITY_APPLICATION);
+        window.set_border_width(8);
+
+        let image = gtk::Image::new();
+        window.add(&image);
+        Self {
+            window,
+            image,
+            always,
+        }
+    }
+
+    /// Show the icon of the tray state, and hide the window while the shown
+    /// source is off unless it is always shown.
+    pub fn draw(&self, state: &TrayState) {
+        if self.always || state.active() {
+            self.image
+                .set_from_icon_name(Some(state.icon().0), gtk::IconSize::Dnd);
+            self.window.show_all();
+        } else {
+            self.window.hide();
+        }
+    }
+}
@@ -0,0 +1,59 @@
+//! Simple shell-style wildcard patterns.
+
+/// Check whether `text` matches `pattern`, where `*` matches any number of
+/// characters and `?` matches exactly one character.
+pub fn matches(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // The position of the last `*` in the pattern, and where in the text it started to match.
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            // Let the last `*` match one more character and try again.
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn literal() {
+        assert!(matches("alsa_input.usb", "alsa_input.usb"));
+        assert!(!matches("alsa_input.usb", "alsa_input.usb-2"));
+        assert!(!matches("alsa_input.usb", "alsa_input"));
+    }
+
+    #[test]
+    fn star_matches_any_number_of_characters() {
+        assert!(matches("*.monitor", "alsa_output.pci.monitor"));
+        assert!(matches("alsa_*", "alsa_"));
+        assert!(matches("*usb*", "alsa_input.usb-Blue_Yeti"));
+        assert!(matches("*", ""));
+        assert!(matches("a*b*c", "aXbYbZc"));
+        assert!(!matches("*.monitor", "alsa_output.pci"));
+        assert!(!matches("a*b", "aXbYc"));
+    }
+
+    #[test]
+    fn question_mark_matches_one_character() {
+        assert!(matches("hw:?", "hw:1"));
+        assert!(!matches("hw:?", "hw:"));
+        assert!(!matches("hw:?", "hw:10"));
+        assert!(matches("Mikrofon ?", "Mikrofon ü"));
+    }
+}
@@ -0,0 +1,111 @@
+//! Watching the registry for the most preferred of several sources.
+
+use anyhow::Result;
+use pipewire::core::Core;
+use pipewire::keys;
+use pipewire::registry::{self, Registry};
+use pipewire::types::ObjectType;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+/// Keeps the registry listener alive.
+pub struct PreferenceWatcher {
+    _registry: Registry,
+    _listener: registry::Listener,
+    state: Rc<RefCell<State>>,
+}
+
+impl PreferenceWatcher {
+    /// Get the id of the most preferred node that is present, if any.
+    pub fn best(&self) -> Option<u32> {
+        self.state.borrow().best().map(|(id, _)| id)
+    }
+}
+
+#[derive(Default)]
+struct State {
+    /// The names of all present preferred nodes, by their rank and id.
+    nodes: BTreeMap<(usize, u32), String>,
+}
+
+impl State {
+    fn best(&self) -> Option<(u32, String)> {
+        self.nodes
+            .first_key_value()
+            .map(|(&(_, id), name)| (id, name.clone()))
+    }
+}
+
+/// Call `on_change` with the id and name of the most preferred node that is
+/// present, whenever that changes, or with None once none of them is present.
+///
+/// Only nodes with the given media class are considered. They are matched
+/// against the `preferred` list by node name or object serial, where earlier
+/// entries are preferred over later ones.
+pub fn watch(
+    core: &Core,
+    media_class: &'static str,
+    preferred: Vec<String>,
+    on_change: impl Fn(Option<(u32, String)>) + 'static,
+) -> Result<PreferenceWatcher> {
+    let registry = core.get_registry()?;
+    let state: Rc<RefCell<State>> = Rc::default();
+    let on_change = Rc::new(on_change);
+
+    let listener = registry
+        .add_listener_local()
+        .global({
+            let state = state.clone();
+            let on_change = on_change.clone();
+            move |global| {
+                if global.type_ != ObjectType::Node {
+                    return;
+                }
+                let Some(props) = global.props else {
+                    return;
+                };
+                if props.get(*keys::MEDIA_CLASS) != Some(media_class) {
+                    return;
+                }
+                let name = props.get(*keys::NODE_NAME);
+                let serial = props.get(*keys::OBJECT_SERIAL);
+                let Some(rank) = preferred
+                    .iter()
+                    .position(|p| name == Some(p.as_str()) || serial == Some(p.as_str()))
+                else {
+                    return;
+                };
+                let mut state = state.borrow_mut();
+                let old = state.best();
+                state
+                    .nodes
+                    .insert((rank, global.id), name.unwrap_or_default().to_owned());
+                let new = state.best();
+                drop(state);
+                if new != old {
+                    on_change(new);
+                }
+            }
+        })
+        .global_remove({
+            let state = state.clone();
+            move |id| {
+                let mut state = state.borrow_mut();
+                let old = state.best();
+                state.nodes.retain(|&(_, node), _| node != id);
+                let new = state.best();
+                drop(state);
+                if new != old {
+                    on_change(new);
+                }
+            }
+        })
+        .register();
+
+    Ok(PreferenceWatcher {
+        _registry: registry,
+        _listener: listener,
+        state,
+    })
+}
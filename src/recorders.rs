@@ -0,0 +1,97 @@
+//! Watching the registry for other applications that capture audio or video.
+
+use anyhow::Result;
+use pipewire::core::Core;
+use pipewire::keys;
+use pipewire::registry::{self, Registry};
+use pipewire::types::ObjectType;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::Arc;
+
+/// Keeps the registry listener alive.
+pub struct RecorderWatcher {
+    _registry: Registry,
+    _listener: registry::Listener,
+}
+
+#[derive(Default)]
+struct State {
+    /// The application names of all clients.
+    clients: HashMap<u32, String>,
+    /// The application names of all capture streams.
+    recorders: HashMap<u32, Arc<str>>,
+}
+
+/// Call `on_change` with the application's name and true whenever another
+/// application starts a capture stream of the given media class (like
+/// "Stream/Input/Audio" or "Stream/Input/Video"), and with false when it stops
+/// again.
+///
+/// Streams of this process and level meters (like pavucontrol's) are ignored.
+pub fn watch(
+    core: &Core,
+    media_class: &'static str,
+    on_change: impl Fn(Arc<str>, bool) + 'static,
+) -> Result<RecorderWatcher> {
+    let registry = core.get_registry()?;
+    let state: Rc<RefCell<State>> = Rc::default();
+    let on_change = Rc::new(on_change);
+    let own_pid = std::process::id().to_string();
+
+    let listener = registry
+        .add_listener_local()
+        .global({
+            let state = state.clone();
+            let on_change = on_change.clone();
+            move |global| {
+                let Some(props) = global.props else {
+                    return;
+                };
+                let mut state = state.borrow_mut();
+                if global.type_ == ObjectType::Client {
+                    if let Some(name) = props.get(*keys::APP_NAME) {
+                        state.clients.insert(global.id, name.to_owned());
+                    }
+                    return;
+                }
+                if global.type_ != ObjectType::Node
+                    || props.get(*keys::MEDIA_CLASS) != Some(media_class)
+                    || props.get(*keys::APP_PROCESS_ID) == Some(own_pid.as_str())
+                    || props.get(*keys::STREAM_MONITOR) == Some("true")
+                {
+                    return;
+                }
+                let client_name = props
+                    .get(*keys::CLIENT_ID)
+                    .and_then(|id| id.parse().ok())
+                    .and_then(|id: u32| state.clients.get(&id));
+                let name: Arc<str> = props
+                    .get(*keys::APP_NAME)
+                    .or(client_name.map(String::as_str))
+                    .or(props.get(*keys::MEDIA_NAME))
+                    .or(props.get(*keys::NODE_NAME))
+                    .unwrap_or("An application")
+                    .into();
+                if state.recorders.insert(global.id, name.clone()).is_none() {
+                    drop(state);
+                    on_change(name, true);
+                }
+            }
+        })
+        .global_remove(move |id| {
+            let mut state = state.borrow_mut();
+            state.clients.remove(&id);
+            if let Some(name) = state.recorders.remove(&id) {
+                drop(state);
+                on_change(name, false);
+            }
+        })
+        .register();
+
+    Ok(RecorderWatcher {
+        _registry: registry,
+        _listener: listener,
+    })
+}
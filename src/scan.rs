@@ -0,0 +1,87 @@
+//! Scanning of sample buffers on the realtime thread.
+//!
+//! The loops here keep several independent accumulators, so that the compiler
+//! is able to auto-vectorize them.
+
+const LANES: usize = 8;
+
+/// Calculate the peak absolute value and the sum of squares of the samples.
+pub fn peak_and_power(samples: &[f32]) -> (f32, f32) {
+    let mut peak = [0f32; LANES];
+    let mut power = [0f32; LANES];
+    let chunks = samples.chunks_exact(LANES);
+    let remainder = chunks.remainder();
+    for chunk in chunks {
+        for ((sample, max), sum) in chunk.iter().zip(&mut peak).zip(&mut power) {
+            let abs = sample.abs();
+            *max = if abs > *max { abs } else { *max };
+            *sum += sample * sample;
+        }
+    }
+    for ((sample, max), sum) in remainder.iter().zip(&mut peak).zip(&mut power) {
+        *max = max.max(sample.abs());
+        *sum += sample * sample;
+    }
+    (
+        peak.into_iter().fold(0f32, f32::max),
+        power.into_iter().sum(),
+    )
+}
+
+/// Calculate the sum of squares of each channel of interleaved samples.
+///
+/// The number of channels is given by the length of `powers`.
+pub fn channel_powers(samples: &[f32], powers: &mut [f32]) {
+    powers.fill(0.);
+    for frame in samples.chunks_exact(powers.len()) {
+        for (sum, sample) in powers.iter_mut().zip(frame) {
+            *sum += sample * sample;
+        }
+    }
+}
+
+/// Count how often consecutive samples of the same channel change their sign,
+/// averaged over all channels of interleaved samples.
+pub fn zero_crossings(samples: &[f32], n_channels: usize) -> u32 {
+    let crossings: u32 = samples
+        .iter()
+        .zip(&samples[n_channels.min(samples.len())..])
+        .map(|(a, b)| ((*a < 0.) != (*b < 0.)) as u32)
+        .sum();
+    crossings / n_channels as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn peak_and_power_include_the_remainder() {
+        // More samples than lanes, with the peak in the remainder.
+        let mut samples = vec![0.5f32; LANES + 3];
+        samples[LANES + 1] = -0.9;
+        let (peak, power) = peak_and_power(&samples);
+        assert_eq!(peak, 0.9);
+        let expected = (LANES + 2) as f32 * 0.25 + 0.81;
+        assert!((power - expected).abs() < 1e-5);
+        assert_eq!(peak_and_power(&[]), (0., 0.));
+    }
+
+    #[test]
+    fn channel_powers_of_interleaved_samples() {
+        let samples = [1., 0.5, -1., 0.5, 1., -0.5];
+        let mut powers = [0.; 2];
+        channel_powers(&samples, &mut powers);
+        assert_eq!(powers, [3., 0.75]);
+    }
+
+    #[test]
+    fn zero_crossings_per_channel() {
+        // The left channel changes its sign at every frame, the right one
+        // never, which averages to one crossing per channel.
+        let samples = [1., 1., -1., 1., 1., 1., -1., 1.];
+        assert_eq!(zero_crossings(&samples, 2), 1);
+        assert_eq!(zero_crossings(&[1., -1., 1., -1.], 1), 3);
+        assert_eq!(zero_crossings(&[1.], 2), 0);
+    }
+}
@@ -0,0 +1,184 @@
+//! Time-of-day schedules, which use different thresholds or sounds for parts of the day.
+
+use anyhow::Result;
+use serde::Deserialize;
+
+/// A time of day, in minutes since midnight.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(try_from = "String")]
+pub struct TimeOfDay(u32);
+
+impl TimeOfDay {
+    pub fn new(hour: u32, minute: u32) -> Self {
+        Self(hour * 60 + minute)
+    }
+
+    /// Get the current local time of day.
+    pub fn now() -> Result<Self> {
+        let now = glib::DateTime::now_local()?;
+        Ok(Self::new(now.hour() as u32, now.minute() as u32))
+    }
+}
+
+impl TryFrom<String> for TimeOfDay {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        let Some((hour, minute)) = value.split_once(':') else {
+            anyhow::bail!("expected a time like \"22:00\", got {value:?}");
+        };
+        let (hour, minute): (u32, u32) = (hour.parse()?, minute.parse()?);
+        if hour > 24 || minute > 59 || (hour == 24 && minute > 0) {
+            anyhow::bail!("time out of range: {value:?}");
+        }
+        Ok(Self::new(hour, minute))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScheduleEntry {
+    /// When the entry starts to apply.
+    pub start: TimeOfDay,
+    /// When the entry stops to apply. May be before `start` to wrap around midnight.
+    pub end: TimeOfDay,
+    /// The threshold in dB, like `--threshold`.
+    pub threshold: Option<f32>,
+    /// The threshold in dB that turns the "on" state on, like `--threshold-on`.
+    pub threshold_on: Option<f32>,
+    /// The threshold in dB that keeps the "on" state on, like `--threshold-off`.
+    pub threshold_off: Option<f32>,
+    /// Whether to play sounds.
+    #[serde(default = "default_sounds")]
+    pub sounds: bool,
+}
+
+fn default_sounds() -> bool {
+    true
+}
+
+impl ScheduleEntry {
+    pub fn threshold_on(&self) -> Option<f32> {
+        self.threshold_on.or(self.threshold)
+    }
+
+    pub fn threshold_off(&self) -> Option<f32> {
+        self.threshold_off.or(self.threshold)
+    }
+
+    /// Check whether the entry applies at the given time of day.
+    pub fn contains(&self, time: TimeOfDay) -> bool {
+        in_range(self.start, self.end, time)
+    }
+}
+
+/// A time window during which no sounds are played, regardless of the schedule.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct QuietHours {
+    pub start: TimeOfDay,
+    /// May be before `start` to wrap around midnight.
+    pub end: TimeOfDay,
+}
+
+impl QuietHours {
+    pub fn contains(&self, time: TimeOfDay) -> bool {
+        in_range(self.start, self.end, time)
+    }
+}
+
+/// Check whether a time of day is between `start` (inclusive) and `end`
+/// (exclusive), wrapping around midnight if `end` is before `start`.
+fn in_range(start: TimeOfDay, end: TimeOfDay, time: TimeOfDay) -> bool {
+    if start <= end {
+        start <= time && time < end
+    } else {
+        start <= time || time < end
+    }
+}
+
+/// Find the first entry that applies at the given time of day.
+pub fn active(schedule: &[ScheduleEntry], time: TimeOfDay) -> Option<&ScheduleEntry> {
+    schedule.iter().find(|entry| entry.contains(time))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(value: &str) -> TimeOfDay {
+        TimeOfDay::try_from(value.to_owned()).unwrap()
+    }
+
+    fn entry(start: &str, end: &str, threshold: f32) -> ScheduleEntry {
+        ScheduleEntry {
+            start: time(start),
+            end: time(end),
+            threshold: Some(threshold),
+            threshold_on: None,
+            threshold_off: None,
+            sounds: true,
+        }
+    }
+
+    #[test]
+    fn parse_time_of_day() {
+        assert_eq!(time("22:30"), TimeOfDay::new(22, 30));
+        assert_eq!(time("24:00"), TimeOfDay::new(24, 0));
+        for invalid in ["22", "22:60", "24:01", "25:00", "a:00", "-1:00"] {
+            assert!(
+                TimeOfDay::try_from(invalid.to_owned()).is_err(),
+                "{invalid}"
+            );
+        }
+    }
+
+    #[test]
+    fn range_includes_start_and_excludes_end() {
+        let entry = entry("08:00", "18:00", -40.);
+        assert!(!entry.contains(time("07:59")));
+        assert!(entry.contains(time("08:00")));
+        assert!(entry.contains(time("17:59")));
+        assert!(!entry.contains(time("18:00")));
+    }
+
+    #[test]
+    fn range_wraps_around_midnight() {
+        let quiet = QuietHours {
+            start: time("22:00"),
+            end: time("07:00"),
+        };
+        assert!(quiet.contains(time("23:00")));
+        assert!(quiet.contains(time("00:00")));
+        assert!(quiet.contains(time("06:59")));
+        assert!(!quiet.contains(time("07:00")));
+        assert!(!quiet.contains(time("12:00")));
+    }
+
+    #[test]
+    fn first_matching_entry_is_active() {
+        let schedule = [entry("08:00", "18:00", -40.), entry("00:00", "24:00", -30.)];
+        let threshold = |at| active(&schedule, time(at)).and_then(|e| e.threshold_on());
+        assert_eq!(threshold("12:00"), Some(-40.));
+        assert_eq!(threshold("20:00"), Some(-30.));
+        assert!(active(&schedule[..1], time("20:00")).is_none());
+    }
+
+    #[test]
+    fn separate_thresholds_override_the_common_one() {
+        let mut entry = entry("08:00", "18:00", -40.);
+        entry.threshold_off = Some(-45.);
+        assert_eq!(entry.threshold_on(), Some(-40.));
+        assert_eq!(entry.threshold_off(), Some(-45.));
+    }
+
+    #[test]
+    fn deserialize_entry() {
+        let entry: ScheduleEntry =
+            toml::from_str("start = \"22:00\"\nend = \"07:00\"\nsounds = false").unwrap();
+        assert_eq!(entry.start, TimeOfDay::new(22, 0));
+        assert!(!entry.sounds);
+        assert!(toml::from_str::<ScheduleEntry>("start = \"22:00\"").is_err());
+        assert!(toml::from_str::<ScheduleEntry>("start = \"25:00\"\nend = \"07:00\"").is_err());
+    }
+}
@@ -0,0 +1,99 @@
+//! Watching the logind session for the screen being locked and unlocked.
+
+use anyhow::{Context, Result};
+use gio::{BusType, DBusCallFlags, DBusConnection, DBusSignalFlags};
+use glib::{ToVariant, VariantDict};
+use std::cell::Cell;
+use std::rc::Rc;
+
+const LOGIND: &str = "org.freedesktop.login1";
+const MANAGER_PATH: &str = "/org/freedesktop/login1";
+const MANAGER_INTERFACE: &str = "org.freedesktop.login1.Manager";
+const SESSION_INTERFACE: &str = "org.freedesktop.login1.Session";
+
+/// Find the object path of the logind session that this process belongs to.
+fn session_path(bus: &DBusConnection) -> Result<String> {
+    // Services of the user manager do not belong to a session themselves, but
+    // usually inherit the session id of the graphical session.
+    let (method, parameters) = match std::env::var("XDG_SESSION_ID") {
+        Ok(id) => ("GetSession", (id,).to_variant()),
+        Err(_) => ("GetSessionByPID", (std::process::id(),).to_variant()),
+    };
+    let reply = bus.call_sync(
+        Some(LOGIND),
+        MANAGER_PATH,
+        MANAGER_INTERFACE,
+        method,
+        Some(&parameters),
+        None,
+        DBusCallFlags::NONE,
+        -1,
+        None::<&gio::Cancellable>,
+    )?;
+    reply
+        .child_value(0)
+        .str()
+        .map(str::to_owned)
+        .context("unexpected reply from logind")
+}
+
+/// Run a main loop that calls `on_lock_change` with whether the session is
+/// locked, whenever that changes.
+///
+/// Both the Lock and Unlock signals and the LockedHint property are watched,
+/// as screen lockers differ in which of them they use.
+pub fn thread_main(on_lock_change: impl Fn(bool) + 'static) {
+    if let Err(e) = run(on_lock_change) {
+        eprintln!("error: cannot watch the session for locking: {e}");
+    }
+}
+
+fn run(on_lock_change: impl Fn(bool) + 'static) -> Result<()> {
+    let context = glib::MainContext::new();
+    context
+        .with_thread_default(|| -> Result<()> {
+            let bus = gio::bus_get_sync(BusType::System, None::<&gio::Cancellable>)?;
+            let path = session_path(&bus)?;
+            let locked = Rc::new(Cell::new(false));
+            let on_lock_change = Rc::new(on_lock_change);
+            let update = move |is_locked: bool| {
+                if locked.replace(is_locked) != is_locked {
+                    on_lock_change(is_locked);
+                }
+            };
+
+            let _signals = bus.signal_subscribe(
+                Some(LOGIND),
+                Some(SESSION_INTERFACE),
+                None,
+                Some(&path),
+                None,
+                DBusSignalFlags::NONE,
+                {
+                    let update = update.clone();
+                    move |_, _, _, _, signal, _| match signal {
+                        "Lock" => update(true),
+                        "Unlock" => update(false),
+                        _ => {}
+                    }
+                },
+            );
+            let _properties = bus.signal_subscribe(
+                Some(LOGIND),
+                Some("org.freedesktop.DBus.Properties"),
+                Some("PropertiesChanged"),
+                Some(&path),
+                Some(SESSION_INTERFACE),
+                DBusSignalFlags::NONE,
+                move |_, _, _, _, _, parameters| {
+                    let changed = VariantDict::new(Some(&parameters.child_value(1)));
+                    if let Ok(Some(locked)) = changed.lookup::<bool>("LockedHint") {
+                        update(locked);
+                    }
+                },
+            );
+            glib::MainLoop::new(Some(&context), false).run();
+            Ok(())
+        })
+        .context("cannot use the main context")?
+}
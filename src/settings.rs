@@ -0,0 +1,144 @@
+//! The settings window, which writes its changes to the config file.
+
+use crate::config::{self, Settings};
+use crate::tray::TrayActions;
+use gtk::prelude::*;
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::Duration;
+
+/// Create the settings window, showing `settings`, which are replaced when
+/// the user saves their changes.
+pub fn window(
+    settings: Rc<RefCell<Settings>>,
+    config_path: Option<PathBuf>,
+    actions: TrayActions,
+) -> gtk::Window {
+    let current = settings.borrow().clone();
+    let window = gtk::Window::new(gtk::WindowType::Toplevel);
+    window.set_title("pw-micclick settings");
+    window.set_border_width(12);
+    window.set_resizable(false);
+
+    let threshold = gtk::SpinButton::with_range(-100., 0., 1.);
+    threshold.set_value(current.threshold.into());
+    let hold_time = gtk::SpinButton::with_range(0., 5000., 50.);
+    hold_time.set_value(current.hold_time.as_millis() as f64);
+    let on_sound = sound_chooser("Choose the on sound", &current.on_sound);
+    let off_sound = sound_chooser("Choose the off sound", &current.off_sound);
+    let device = gtk::ComboBoxText::new();
+    device.append(Some(""), "Default source");
+    for node in actions.sources() {
+        device.append(Some(&node.name), &node.description);
+    }
+    let target = current.target.as_deref().unwrap_or_default();
+    if !device.set_active_id(Some(target)) {
+        // The source is not present right now, but keep it selected.
+        device.append(Some(target), target);
+        device.set_active_id(Some(target));
+    }
+
+    let grid = gtk::Grid::new();
+    grid.set_row_spacing(6);
+    grid.set_column_spacing(12);
+    let rows: [(&str, &gtk::Widget); 5] = [
+        ("Threshold (dB)", threshold.upcast_ref()),
+        ("Hold time (ms)", hold_time.upcast_ref()),
+        ("On sound", on_sound.upcast_ref()),
+        ("Off sound", off_sound.upcast_ref()),
+        ("Source", device.upcast_ref()),
+    ];
+    for (row, (label, widget)) in rows.into_iter().enumerate() {
+        let label = gtk::Label::new(Some(label));
+        label.set_halign(gtk::Align::Start);
+        grid.attach(&label, 0, row as i32, 1, 1);
+        grid.attach(widget, 1, row as i32, 1, 1);
+    }
+    let note = gtk::Label::new(Some("New sounds and sources are used after a restart."));
+    note.style_context().add_class("dim-label");
+    grid.attach(&note, 0, rows.len() as i32, 2, 1);
+
+    let cancel = gtk::Button::with_label("Cancel");
+    cancel.connect_clicked({
+        let window = window.clone();
+        move |_| window.close()
+    });
+    let save = gtk::Button::with_label("Save");
+    save.connect_clicked({
+        let window = window.clone();
+        move |_| {
+            let new = Settings {
+                threshold: threshold.value() as f32,
+                hold_time: Duration::from_millis(hold_time.value() as u64),
+                on_sound: chosen_sound(&on_sound, &current.on_sound),
+                off_sound: chosen_sound(&off_sound, &current.off_sound),
+                target: device
+                    .active_id()
+                    .filter(|id| !id.is_empty())
+                    .map(String::from),
+            };
+            if save_and_apply(&window, &settings, config_path.as_deref(), &actions, new) {
+                window.close();
+            }
+        }
+    });
+    let buttons = gtk::ButtonBox::new(gtk::Orientation::Horizontal);
+    buttons.set_layout(gtk::ButtonBoxStyle::End);
+    buttons.set_spacing(6);
+    buttons.add(&cancel);
+    buttons.add(&save);
+
+    let content = gtk::Box::new(gtk::Orientation::Vertical, 12);
+    content.add(&grid);
+    content.add(&buttons);
+    window.add(&content);
+    window
+}
+
+/// Write the new settings to the config file and apply them, or show an
+/// error dialog on top of `window` and return false if they cannot be saved.
+pub fn save_and_apply(
+    window: &gtk::Window,
+    settings: &RefCell<Settings>,
+    config_path: Option<&Path>,
+    actions: &TrayActions,
+    new: Settings,
+) -> bool {
+    if let Err(e) = config::save(config_path, &new) {
+        eprintln!("error: cannot save the settings: {e:#}");
+        let dialog = gtk::MessageDialog::new(
+            Some(window),
+            gtk::DialogFlags::MODAL,
+            gtk::MessageType::Error,
+            gtk::ButtonsType::Close,
+            &format!("Cannot save the settings: {e:#}"),
+        );
+        dialog.connect_response(|dialog, _| dialog.close());
+        dialog.show();
+        return false;
+    }
+    actions.set_threshold(new.threshold);
+    actions.set_hold_time(new.hold_time);
+    *settings.borrow_mut() = new;
+    true
+}
+
+/// Create a button for choosing a sound file, which starts out with the
+/// current sound if that is a file.
+pub fn sound_chooser(title: &str, current: &Option<String>) -> gtk::FileChooserButton {
+    let chooser = gtk::FileChooserButton::new(title, gtk::FileChooserAction::Open);
+    if let Some(path) = current.as_deref().map(Path::new).filter(|p| p.is_file()) {
+        chooser.set_filename(path);
+    }
+    chooser
+}
+
+/// Get the sound that the user chose, or keep the current one, which might
+/// also be a directory or a sound theme name that the chooser cannot show.
+pub fn chosen_sound(chooser: &gtk::FileChooserButton, current: &Option<String>) -> Option<String> {
+    match chooser.filename() {
+        Some(path) => Some(path.to_string_lossy().into_owned()),
+        None => current.clone(),
+    }
+}
@@ -0,0 +1,235 @@
+//! The tray icon, implemented as a StatusNotifierItem with ksni.
+
+use crate::tray::{self, TrayActions, TrayState, Update};
+use crate::{fallback_icons, Event};
+use ksni::menu::{RadioGroup, RadioItem, StandardItem, SubMenu};
+use ksni::{MenuItem, Status, ToolTip, TrayService};
+
+/// The main tray icon, as last drawn from the `TrayState`.
+struct MicTray {
+    actions: TrayActions,
+    /// The thresholds that were adjusted in the tray, to show them briefly.
+    notice_sender: async_channel::Sender<f32>,
+    icon: String,
+    icon_theme_path: String,
+    visible: bool,
+    attention: bool,
+    title: String,
+    pause_label: &'static str,
+    silence_label: &'static str,
+    can_select_source: bool,
+}
+
+impl MicTray {
+    fn draw(&mut self, state: &TrayState) {
+        self.icon = state.icon().0.to_owned();
+        self.visible = state.visible();
+        self.attention = state.needs_attention();
+        self.title = state.title();
+        self.pause_label = state.pause_label();
+        self.silence_label = state.silence_label();
+        self.can_select_source = state.can_select_source();
+    }
+}
+
+impl ksni::Tray for MicTray {
+    fn id(&self) -> String {
+        "pw-micclick".to_owned()
+    }
+
+    fn title(&self) -> String {
+        self.title.clone()
+    }
+
+    fn icon_name(&self) -> String {
+        self.icon.clone()
+    }
+
+    fn icon_theme_path(&self) -> String {
+        self.icon_theme_path.clone()
+    }
+
+    fn attention_icon_name(&self) -> String {
+        "dialog-warning-symbolic".to_owned()
+    }
+
+    fn status(&self) -> Status {
+        if !self.visible {
+            Status::Passive
+        } else if self.attention {
+            Status::NeedsAttention
+        } else {
+            Status::Active
+        }
+    }
+
+    fn tool_tip(&self) -> ToolTip {
+        ToolTip {
+            title: self.title.clone(),
+            ..Default::default()
+        }
+    }
+
+    fn secondary_activate(&mut self, _x: i32, _y: i32) {
+        self.actions.toggle_pause();
+    }
+
+    fn menu(&self) -> Vec<MenuItem<Self>> {
+        let item = |label: &str, activate: fn(&mut Self)| -> MenuItem<Self> {
+            StandardItem {
+                label: label.to_owned(),
+                activate: Box::new(activate),
+                ..Default::default()
+            }
+            .into()
+        };
+        let threshold_items: Vec<MenuItem<Self>> = tray::threshold_steps()
+            .into_iter()
+            .map(|(label, step)| {
+                StandardItem {
+                    label,
+                    activate: Box::new(move |tray: &mut Self| {
+                        tray.actions.adjust_threshold(step, &tray.notice_sender)
+                    }),
+                    ..Default::default()
+                }
+                .into()
+            })
+            .collect();
+        let nodes = self.actions.sources();
+        let selected = self.actions.selected_source();
+        let mut options = vec![RadioItem {
+            label: tray::AUTOMATIC_SOURCE.to_owned(),
+            ..Default::default()
+        }];
+        options.extend(nodes.iter().map(|node| RadioItem {
+            label: node.description.clone(),
+            ..Default::default()
+        }));
+        let ids: Vec<u32> = nodes.iter().map(|node| node.id).collect();
+        let source_group = RadioGroup {
+            // The first option chooses the source automatically.
+            selected: ids
+                .iter()
+                .position(|&id| Some(id) == selected)
+                .map_or(0, |index| index + 1),
+            select: Box::new(move |tray: &mut Self, index| {
+                tray.actions
+                    .select_source(index.checked_sub(1).map(|index| ids[index]))
+            }),
+            options,
+        };
+        vec![
+            item(self.pause_label, |tray| tray.actions.toggle_pause()),
+            item(self.silence_label, |tray| tray.actions.toggle_silenced()),
+            SubMenu {
+                label: self.actions.threshold_label(),
+                submenu: threshold_items,
+                ..Default::default()
+            }
+            .into(),
+            SubMenu {
+                label: "Source".to_owned(),
+                enabled: self.can_select_source,
+                submenu: vec![source_group.into()],
+                ..Default::default()
+            }
+            .into(),
+            item("Test on sound", |tray| tray.actions.test_sound(true)),
+            item("Test off sound", |tray| tray.actions.test_sound(false)),
+            item("Open configuration", |tray| tray.actions.open_config()),
+            item("Reload configuration", |tray| tray.actions.reload_config()),
+            MenuItem::Separator,
+            item("Quit", |tray| tray.actions.quit()),
+        ]
+    }
+}
+
+/// The camera tray icon, shown while any application uses a camera.
+struct CameraTray {
+    title: Option<String>,
+    icon_theme_path: String,
+}
+
+impl ksni::Tray for CameraTray {
+    fn id(&self) -> String {
+        "pw-micclick-camera".to_owned()
+    }
+
+    fn title(&self) -> String {
+        self.title.clone().unwrap_or_default()
+    }
+
+    fn icon_name(&self) -> String {
+        "camera-web-symbolic".to_owned()
+    }
+
+    fn icon_theme_path(&self) -> String {
+        self.icon_theme_path.clone()
+    }
+
+    fn status(&self) -> Status {
+        if self.title.is_some() {
+            Status::Active
+        } else {
+            Status::Passive
+        }
+    }
+}
+
+pub fn thread_main(
+    eventreceiver: async_channel::Receiver<Event>,
+    mut state: TrayState,
+    actions: TrayActions,
+) {
+    let icon_theme_path = fallback_icons::install()
+        .map(|path| path.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let (notice_sender, notices) = async_channel::unbounded();
+    let mut tray = MicTray {
+        actions,
+        notice_sender,
+        icon: String::new(),
+        icon_theme_path: icon_theme_path.clone(),
+        visible: false,
+        attention: false,
+        title: String::new(),
+        pause_label: "",
+        silence_label: "",
+        can_select_source: false,
+    };
+    tray.draw(&state);
+    let service = TrayService::new(tray);
+    let handle = service.handle();
+    service.spawn();
+    let camera_service = TrayService::new(CameraTray {
+        title: None,
+        icon_theme_path,
+    });
+    let camera_handle = camera_service.handle();
+    camera_service.spawn();
+
+    let context = glib::MainContext::new();
+    let result = context.with_thread_default(|| {
+        context.block_on(async {
+            while let Some(update) = state.next_update(&eventreceiver, &notices).await {
+                match update {
+                    Update::Nothing => {}
+                    Update::Cameras => {
+                        let title = state.camera_title();
+                        camera_handle.update(|tray| tray.title = title);
+                    }
+                    // The menu is rebuilt whenever the tray is updated.
+                    Update::Sources => handle.update(|_| {}),
+                    Update::Level if !state.args().tray_level => {}
+                    Update::Icon | Update::Level => handle.update(|tray| tray.draw(&state)),
+                }
+            }
+        })
+    });
+    if let Err(e) = result {
+        eprintln!("error: cannot use the main context for the tray icon: {e}");
+    }
+    handle.shutdown();
+    camera_handle.shutdown();
+}
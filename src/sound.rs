@@ -0,0 +1,648 @@
+//! Decoding sound files and playing them through PipeWire playback streams.
+
+use crate::sound_theme;
+use anyhow::Result;
+use libspa::pod::Pod;
+use libspa::utils::Direction;
+use libspa_sys::*;
+use pipewire::core::{Core, Listener, PW_ID_CORE};
+use pipewire::keys;
+use pipewire::properties::properties;
+use pipewire::stream::{Stream, StreamFlags, StreamListener, StreamState};
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+use std::ffi::{c_char, c_int, c_void, CStr, CString};
+use std::mem::zeroed;
+use std::path::Path;
+use std::rc::{Rc, Weak};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A decoded sound.
+pub struct Sound {
+    /// The interleaved samples.
+    samples: Vec<f32>,
+    channels: u32,
+    rate: u32,
+}
+
+impl Sound {
+    /// Decode a sound file into memory.
+    ///
+    /// A path like "theme:bell" names a sound from the desktop's sound theme.
+    pub fn load(path: &str) -> Result<Self> {
+        match path.strip_prefix("theme:") {
+            Some(name) => Self::decode(&sound_theme::lookup(name)?.to_string_lossy()),
+            None => Self::decode(path),
+        }
+    }
+
+    /// Decode a sound file with libsndfile, or with symphonia for formats that
+    /// the installed libsndfile does not support (e.g. MP3 before version 1.1).
+    /// Opus is only supported by libsndfile.
+    fn decode(path: &str) -> Result<Self> {
+        let sndfile_error = match sndfile::read(path) {
+            Ok(sound) => return Ok(sound),
+            Err(e) => e,
+        };
+        // Symphonia has no Opus decoder, so only libsndfile can read those.
+        let opus = Path::new(path)
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("opus"));
+        if opus {
+            anyhow::bail!(
+                "cannot decode Opus file, which needs libsndfile 1.0.29 or newer \
+                 (libsndfile: {sndfile_error})"
+            );
+        }
+        decoder::read(path).map_err(|e| {
+            anyhow::anyhow!(
+                "unsupported or broken sound file (libsndfile: {sndfile_error}; symphonia: {e})"
+            )
+        })
+    }
+
+    /// Decode a sound file, or all sound files in a directory.
+    ///
+    /// Files in a directory that cannot be decoded are skipped with a warning,
+    /// so that e.g. a README next to the sounds does not hurt.
+    pub fn load_all(path: &str) -> Result<Vec<Self>> {
+        if !Path::new(path).is_dir() {
+            return Ok(vec![Self::load(path)?]);
+        }
+        let mut files = std::fs::read_dir(path)?
+            .map(|entry| Ok(entry?.path()))
+            .collect::<Result<Vec<_>>>()?;
+        files.sort();
+        let sounds: Vec<Self> = files
+            .iter()
+            .filter(|file| file.is_file())
+            .filter_map(|file| match Self::load(&file.to_string_lossy()) {
+                Ok(sound) => Some(sound),
+                Err(e) => {
+                    eprintln!("warning: skipping {file:?}: {e}");
+                    None
+                }
+            })
+            .collect();
+        if sounds.is_empty() {
+            anyhow::bail!("the directory contains no sound files");
+        }
+        Ok(sounds)
+    }
+
+    /// Get the built-in click for the microphone turning on or off.
+    pub fn default_click(on: bool) -> Self {
+        let bytes: &'static [u8] = if on {
+            include_bytes!("../sounds/click-on.wav")
+        } else {
+            include_bytes!("../sounds/click-off.wav")
+        };
+        decoder::read_bytes(bytes, "wav").expect("the built-in sounds are valid")
+    }
+
+    /// Get how long the sound plays.
+    pub fn duration(&self) -> Duration {
+        let frames = self.samples.len() / self.channels.max(1) as usize;
+        Duration::from_secs_f64(frames as f64 / f64::from(self.rate.max(1)))
+    }
+
+    /// Get a sound that plays this one `count` times, with `gap` of silence in between.
+    pub fn repeated(&self, count: u32, gap: Duration) -> Self {
+        let gap_samples = (self.rate as f32 * gap.as_secs_f32()) as usize * self.channels as usize;
+        let mut samples = Vec::new();
+        for i in 0..count {
+            if i > 0 {
+                samples.resize(samples.len() + gap_samples, 0.);
+            }
+            samples.extend_from_slice(&self.samples);
+        }
+        Self {
+            samples,
+            channels: self.channels,
+            rate: self.rate,
+        }
+    }
+
+    /// Synthesize a beep.
+    pub fn tone(tone: Tone) -> Self {
+        let n_samples = (TONE_RATE as f32 * tone.duration.as_secs_f32()) as usize;
+        let fade = ((TONE_RATE as f32 * TONE_FADE.as_secs_f32()) as usize).min(n_samples / 2);
+        let samples = (0..n_samples)
+            .map(|n| {
+                let phase = (tone.frequency * n as f32 / TONE_RATE as f32).fract();
+                let value = match tone.waveform {
+                    Waveform::Sine => (2. * PI * phase).sin(),
+                    Waveform::Square if phase < 0.5 => 1.,
+                    Waveform::Square => -1.,
+                };
+                // Fade in and out, so that the beep does not start or end with a click.
+                let envelope = (n.min(n_samples - n) as f32 / fade.max(1) as f32).min(1.);
+                value * envelope * TONE_AMPLITUDE
+            })
+            .collect();
+        Self {
+            samples,
+            channels: 1,
+            rate: TONE_RATE,
+        }
+    }
+}
+
+/// The sample rate of synthesized beeps.
+const TONE_RATE: u32 = 48000;
+/// How long synthesized beeps fade in and out.
+const TONE_FADE: Duration = Duration::from_millis(5);
+/// The peak amplitude of synthesized beeps, which leaves some headroom.
+const TONE_AMPLITUDE: f32 = 0.5;
+
+#[derive(Debug, Copy, Clone)]
+pub enum Waveform {
+    Sine,
+    Square,
+}
+
+/// A beep that can be synthesized instead of playing a sound file.
+#[derive(Debug, Copy, Clone)]
+pub struct Tone {
+    /// The frequency in Hz.
+    frequency: f32,
+    duration: Duration,
+    waveform: Waveform,
+}
+
+impl FromStr for Tone {
+    type Err = anyhow::Error;
+
+    /// Parse a tone like "880:50ms" or "440:0.1s:square".
+    fn from_str(value: &str) -> Result<Self> {
+        let mut parts = value.split(':');
+        let frequency: f32 = parts
+            .next()
+            .unwrap_or_default()
+            .trim_end_matches("Hz")
+            .parse()?;
+        // Like the other durations, a bare number is in milliseconds.
+        let duration = match parts.next() {
+            None => DEFAULT_TONE_DURATION,
+            Some(duration) => crate::parse_millis(duration)?,
+        };
+        let waveform = match parts.next() {
+            None | Some("sine") => Waveform::Sine,
+            Some("square") => Waveform::Square,
+            Some(other) => anyhow::bail!("unknown waveform {other:?}, expected sine or square"),
+        };
+        if parts.next().is_some() {
+            anyhow::bail!("expected a tone like 880:50ms or 880:50ms:square");
+        }
+        if frequency.is_nan() || frequency <= 0. {
+            anyhow::bail!("the frequency must be positive");
+        }
+        if duration.is_zero() {
+            anyhow::bail!("the duration must not be zero");
+        }
+        Ok(Self {
+            frequency,
+            duration,
+            waveform,
+        })
+    }
+}
+
+/// The duration of a tone that is given only by its frequency.
+const DEFAULT_TONE_DURATION: Duration = Duration::from_millis(50);
+
+/// Bindings to the parts of libsndfile that are needed to decode sound files.
+mod sndfile {
+    use super::*;
+
+    const SFM_READ: c_int = 0x10;
+
+    #[repr(C)]
+    struct SfInfo {
+        frames: i64,
+        samplerate: c_int,
+        channels: c_int,
+        format: c_int,
+        sections: c_int,
+        seekable: c_int,
+    }
+
+    // Linked as found by pkg-config in build.rs.
+    extern "C" {
+        fn sf_open(path: *const c_char, mode: c_int, info: *mut SfInfo) -> *mut c_void;
+        fn sf_readf_float(file: *mut c_void, ptr: *mut f32, frames: i64) -> i64;
+        fn sf_strerror(file: *mut c_void) -> *const c_char;
+        fn sf_close(file: *mut c_void) -> c_int;
+    }
+
+    pub fn read(path: &str) -> Result<Sound> {
+        let c_path = CString::new(path)?;
+        let mut info: SfInfo = unsafe { zeroed() };
+        let file = unsafe { sf_open(c_path.as_ptr(), SFM_READ, &mut info) };
+        if file.is_null() {
+            let error = unsafe { CStr::from_ptr(sf_strerror(file)) };
+            anyhow::bail!("{}", error.to_string_lossy());
+        }
+        let channels = info.channels.max(1) as usize;
+        let mut samples = vec![0f32; info.frames.max(0) as usize * channels];
+        let frames = unsafe { sf_readf_float(file, samples.as_mut_ptr(), info.frames) };
+        unsafe { sf_close(file) };
+        samples.truncate(frames.max(0) as usize * channels);
+        Ok(Sound {
+            samples,
+            channels: channels as u32,
+            rate: info.samplerate as u32,
+        })
+    }
+}
+
+/// Decoding with symphonia, which supports some formats that libsndfile does not.
+mod decoder {
+    use super::*;
+    use anyhow::Context;
+    use std::io::{Cursor, ErrorKind};
+    use symphonia::core::audio::SampleBuffer;
+    use symphonia::core::codecs::DecoderOptions;
+    use symphonia::core::errors::Error;
+    use symphonia::core::formats::FormatOptions;
+    use symphonia::core::io::{MediaSource, MediaSourceStream};
+    use symphonia::core::meta::MetadataOptions;
+    use symphonia::core::probe::Hint;
+
+    pub fn read(path: &str) -> Result<Sound> {
+        let file = std::fs::File::open(path)?;
+        let mut hint = Hint::new();
+        if let Some(extension) = Path::new(path).extension().and_then(|e| e.to_str()) {
+            hint.with_extension(extension);
+        }
+        decode(Box::new(file), &hint)
+    }
+
+    /// Decode a sound that is embedded in the binary.
+    pub fn read_bytes(bytes: &'static [u8], extension: &str) -> Result<Sound> {
+        let mut hint = Hint::new();
+        hint.with_extension(extension);
+        decode(Box::new(Cursor::new(bytes)), &hint)
+    }
+
+    fn decode(source: Box<dyn MediaSource>, hint: &Hint) -> Result<Sound> {
+        let stream = MediaSourceStream::new(source, Default::default());
+        let mut format = symphonia::default::get_probe()
+            .format(
+                hint,
+                stream,
+                &FormatOptions::default(),
+                &MetadataOptions::default(),
+            )?
+            .format;
+        let track = format.default_track().context("no audio track")?;
+        let track_id = track.id;
+        let mut channels = track.codec_params.channels.map_or(0, |c| c.count() as u32);
+        let mut rate = track.codec_params.sample_rate.unwrap_or(0);
+        let mut decoder = symphonia::default::get_codecs()
+            .make(&track.codec_params, &DecoderOptions::default())?;
+
+        let mut samples = Vec::new();
+        loop {
+            let packet = match format.next_packet() {
+                Ok(packet) => packet,
+                Err(Error::IoError(e)) if e.kind() == ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e.into()),
+            };
+            if packet.track_id() != track_id {
+                continue;
+            }
+            let decoded = match decoder.decode(&packet) {
+                Ok(decoded) => decoded,
+                // A corrupt packet only loses a few milliseconds of sound.
+                Err(Error::DecodeError(_)) => continue,
+                Err(e) => return Err(e.into()),
+            };
+            let spec = *decoded.spec();
+            channels = spec.channels.count() as u32;
+            rate = spec.rate;
+            let mut buffer = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+            buffer.copy_interleaved_ref(decoded);
+            samples.extend_from_slice(buffer.samples());
+        }
+        if channels == 0 || rate == 0 {
+            anyhow::bail!("unknown channel count or sample rate");
+        }
+        Ok(Sound {
+            samples,
+            channels,
+            rate,
+        })
+    }
+}
+
+/// The state of one playback stream.
+struct Playback {
+    sound: Arc<Sound>,
+    volume: f32,
+    /// The number of frames to fade in and out over.
+    fade_frames: usize,
+    /// The index of the next sample to play.
+    position: usize,
+    /// Whether all samples were queued, and the stream is waiting for them to be played.
+    draining: bool,
+    /// Set once the stream can be destroyed.
+    done: Rc<Cell<bool>>,
+    player: Weak<Player>,
+}
+
+impl Playback {
+    /// Get the gain for a frame, which fades the sound in at its start and out at its end.
+    fn fade(&self, frame: usize) -> f32 {
+        if self.fade_frames == 0 {
+            return 1.;
+        }
+        let n_frames = self.sound.samples.len() / self.sound.channels as usize;
+        let distance = frame.min(n_frames.saturating_sub(frame + 1));
+        (distance as f32 / self.fade_frames as f32).min(1.)
+    }
+
+    fn finish(&self) {
+        self.done.set(true);
+        let Some(player) = self.player.upgrade() else {
+            return;
+        };
+        // The stream cannot be destroyed from its own callbacks, so have the
+        // core listener destroy it once the server answers.
+        if let Err(e) = player.core.sync(0) {
+            eprintln!("error: cannot destroy a finished playback stream: {e}");
+        }
+        player.play_queued();
+    }
+}
+
+/// What to do with a sound that should be played while another one is still playing.
+#[derive(Debug, Copy, Clone, clap::ValueEnum)]
+pub enum Overlap {
+    /// Play both sounds at the same time.
+    Mix,
+    /// Stop the sounds that are playing, and play the new one.
+    Cut,
+    /// Play the new sound once the others are finished.
+    Queue,
+    /// Do not play the new sound.
+    Drop,
+}
+
+/// Plays sounds through PipeWire, using one playback stream per sound.
+///
+/// The streams use the Notification role, so that the session manager can
+/// route them and control their volume like other notification sounds.
+pub struct Player {
+    core: Core,
+    /// The linear factor by which all samples are scaled.
+    volume: f32,
+    /// How long to fade sounds in and out.
+    fade: Duration,
+    /// The node name or serial of the sink to play to, instead of the default sink.
+    sink: Option<String>,
+    overlap: Overlap,
+    playing: RefCell<Vec<(Stream, StreamListener<Playback>, Rc<Cell<bool>>)>>,
+    /// Sounds that wait for the playing ones to finish, with `Overlap::Queue`.
+    queue: RefCell<VecDeque<Arc<Sound>>>,
+    /// Destroys the finished streams, outside of their callbacks.
+    _core_listener: Listener,
+    this: Weak<Player>,
+}
+
+impl Player {
+    pub fn new(
+        core: &Core,
+        volume: f32,
+        fade: Duration,
+        sink: Option<String>,
+        overlap: Overlap,
+    ) -> Rc<Self> {
+        Rc::new_cyclic(|this| Self {
+            core: core.clone(),
+            volume,
+            fade,
+            sink,
+            overlap,
+            playing: RefCell::default(),
+            queue: RefCell::default(),
+            _core_listener: core
+                .add_listener_local()
+                .done({
+                    let this = this.clone();
+                    move |id, _| {
+                        if let (PW_ID_CORE, Some(player)) = (id, this.upgrade()) {
+                            player.destroy_finished();
+                        }
+                    }
+                })
+                .register(),
+            this: this.clone(),
+        })
+    }
+
+    /// Destroy the streams that finished playing.
+    fn destroy_finished(&self) {
+        let (finished, playing): (Vec<_>, Vec<_>) = self
+            .playing
+            .take()
+            .into_iter()
+            .partition(|(_, _, done)| done.get());
+        *self.playing.borrow_mut() = playing;
+        // Destroying a stream may call its listener, so not while `playing` is borrowed.
+        drop(finished);
+    }
+
+    /// Play a sound, handling any sounds that are still playing according to the overlap policy.
+    pub fn play(&self, sound: Arc<Sound>) -> Result<()> {
+        self.destroy_finished();
+        let busy = !self.playing.borrow().is_empty();
+        match self.overlap {
+            Overlap::Mix => {}
+            Overlap::Cut => {
+                self.queue.borrow_mut().clear();
+                // Like in `destroy_finished`, not while `playing` is borrowed.
+                let cut = self.playing.take();
+                for (stream, _, _) in cut.iter() {
+                    let _ = stream.disconnect();
+                }
+                drop(cut);
+            }
+            Overlap::Queue if busy => {
+                self.queue.borrow_mut().push_back(sound);
+                return Ok(());
+            }
+            Overlap::Queue => {}
+            Overlap::Drop if busy => return Ok(()),
+            Overlap::Drop => {}
+        }
+        self.start(sound)
+    }
+
+    /// Start the next queued sound, if any.
+    ///
+    /// This is called from the callbacks of a finished stream, so it must not
+    /// destroy any streams.
+    fn play_queued(&self) {
+        let Some(sound) = self.queue.borrow_mut().pop_front() else {
+            return;
+        };
+        if let Err(e) = self.start(sound) {
+            eprintln!("error: cannot play sound: {e}");
+        }
+    }
+
+    /// Start playing a sound, in addition to any sounds that are still playing.
+    fn start(&self, sound: Arc<Sound>) -> Result<()> {
+        let mut props = properties! {
+            *keys::MEDIA_TYPE => "Audio",
+            *keys::MEDIA_CATEGORY => "Playback",
+            *keys::MEDIA_ROLE => "Notification",
+        };
+        if let Some(ref sink) = self.sink {
+            props.insert(*keys::TARGET_OBJECT, sink.as_str());
+        }
+        let stream = Stream::new(&self.core, "micclick-sound", props)?;
+        let done: Rc<Cell<bool>> = Rc::default();
+        let state = Playback {
+            sound: sound.clone(),
+            volume: self.volume,
+            fade_frames: (sound.rate as f32 * self.fade.as_secs_f32()) as usize,
+            position: 0,
+            draining: false,
+            done: done.clone(),
+            player: self.this.clone(),
+        };
+        let listener = stream
+            .add_local_listener_with_user_data(state)
+            .process(|stream, state| {
+                let Some(mut buffer) = stream.dequeue_buffer() else {
+                    return;
+                };
+                let channels = state.sound.channels as usize;
+                let stride = std::mem::size_of::<f32>() * channels;
+                let Some(data) = buffer.datas_mut().first_mut() else {
+                    return;
+                };
+                let mut n_frames = 0;
+                if let Some(bytes) = data.data() {
+                    let remaining = &state.sound.samples[state.position..];
+                    n_frames = (bytes.len() / stride).min(remaining.len() / channels);
+                    let samples = &remaining[..n_frames * channels];
+                    let first_frame = state.position / channels;
+                    for (i, (out, sample)) in bytes.chunks_exact_mut(4).zip(samples).enumerate() {
+                        let gain = state.volume * state.fade(first_frame + i / channels);
+                        out.copy_from_slice(&(sample * gain).to_ne_bytes());
+                    }
+                    state.position += samples.len();
+                }
+                let chunk = data.chunk_mut();
+                *chunk.offset_mut() = 0;
+                *chunk.stride_mut() = stride as i32;
+                *chunk.size_mut() = (n_frames * stride) as u32;
+                drop(buffer);
+                if n_frames == 0 && !state.draining {
+                    state.draining = true;
+                    let _ = stream.flush(true);
+                }
+            })
+            .drained(|_, state| state.finish())
+            .state_changed(|_, state, _, new| {
+                if let StreamState::Error(e) = new {
+                    eprintln!("error: sound playback failed: {e}");
+                    state.finish();
+                }
+            })
+            .register()?;
+
+        let mut data = [0u8; 1024];
+        let mut b: spa_pod_builder = unsafe { zeroed() };
+        b.data = data.as_mut_ptr() as *mut c_void;
+        b.size = data.len() as u32;
+        let mut info: spa_audio_info_raw = unsafe { zeroed() };
+        info.format = SPA_AUDIO_FORMAT_F32;
+        info.rate = sound.rate;
+        info.channels = sound.channels;
+        match sound.channels {
+            1 => info.position[0] = SPA_AUDIO_CHANNEL_MONO,
+            2 => {
+                info.position[0] = SPA_AUDIO_CHANNEL_FL;
+                info.position[1] = SPA_AUDIO_CHANNEL_FR;
+            }
+            _ => info.flags = SPA_AUDIO_FLAG_UNPOSITIONED,
+        }
+        let mut params = [unsafe {
+            Pod::from_raw(spa_format_audio_raw_build(
+                &mut b,
+                SPA_PARAM_EnumFormat,
+                &mut info,
+            ))
+        }];
+        stream.connect(
+            Direction::Output,
+            None,
+            StreamFlags::AUTOCONNECT | StreamFlags::MAP_BUFFERS,
+            &mut params,
+        )?;
+        self.playing.borrow_mut().push((stream, listener, done));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_tone() {
+        let tone: Tone = "880:50ms".parse().unwrap();
+        assert_eq!(tone.frequency, 880.);
+        assert_eq!(tone.duration, Duration::from_millis(50));
+        assert!(matches!(tone.waveform, Waveform::Sine));
+        let tone: Tone = "440Hz:0.1s:square".parse().unwrap();
+        assert_eq!(tone.frequency, 440.);
+        assert_eq!(tone.duration, Duration::from_millis(100));
+        assert!(matches!(tone.waveform, Waveform::Square));
+        let tone: Tone = "1000".parse().unwrap();
+        assert_eq!(tone.duration, DEFAULT_TONE_DURATION);
+        let tone: Tone = "880:50".parse().unwrap();
+        assert_eq!(tone.duration, Duration::from_millis(50));
+    }
+
+    #[test]
+    fn reject_invalid_tones() {
+        for invalid in [
+            "",
+            "0",
+            "-440",
+            "beep",
+            "880:-5ms",
+            "880:0",
+            "880:0ms",
+            "880:50ss",
+            "880:50sss",
+            "880:50ms:triangle",
+            "880:50ms:sine:1",
+        ] {
+            assert!(invalid.parse::<Tone>().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn synthesize_tone() {
+        let sound = Sound::tone("1000:10ms:square".parse().unwrap());
+        assert_eq!(sound.channels, 1);
+        assert_eq!(sound.samples.len(), TONE_RATE as usize / 100);
+        // It fades in from silence, and stays within the amplitude.
+        assert_eq!(sound.samples[0], 0.);
+        assert!(sound
+            .samples
+            .iter()
+            .all(|sample| sample.abs() <= TONE_AMPLITUDE));
+        assert!(sound.samples.contains(&TONE_AMPLITUDE));
+        assert_eq!(sound.duration(), Duration::from_millis(10));
+    }
+}
@@ -0,0 +1,133 @@
+//! Looking up sounds by name, following the freedesktop sound theme specification.
+
+use anyhow::{Context, Result};
+use gio::prelude::*;
+use gio::SettingsSchemaSource;
+use std::path::{Path, PathBuf};
+
+/// The theme that every theme implicitly inherits from.
+const FALLBACK_THEME: &str = "freedesktop";
+/// The file extensions that sound files may have, in order of preference.
+const EXTENSIONS: [&str; 3] = ["oga", "ogg", "wav"];
+
+/// Find the file for a sound name like "audio-volume-change" in the current
+/// sound theme.
+///
+/// As in the specification, a name that has no sound of its own falls back to
+/// its more generic prefixes, e.g. "audio-volume" and then "audio", and
+/// sounds that a theme disabled are reported as an error.
+pub fn lookup(name: &str) -> Result<PathBuf> {
+    let dirs = base_dirs();
+    let mut themes = vec![];
+    add_theme(&dirs, &current_theme(), &mut themes);
+    add_theme(&dirs, FALLBACK_THEME, &mut themes);
+
+    let mut name = name;
+    loop {
+        for theme in &themes {
+            for dir in &theme.dirs {
+                if dir.join(format!("{name}.disabled")).exists() {
+                    anyhow::bail!("the sound {name:?} is disabled in the {} theme", theme.name);
+                }
+                for extension in EXTENSIONS {
+                    let path = dir.join(format!("{name}.{extension}"));
+                    if path.is_file() {
+                        return Ok(path);
+                    }
+                }
+            }
+        }
+        name = name
+            .rsplit_once('-')
+            .map(|(prefix, _)| prefix)
+            .with_context(|| format!("no sound named {name:?} in the sound theme"))?;
+    }
+}
+
+struct Theme {
+    name: String,
+    /// The directories with sound files, from all base directories.
+    dirs: Vec<PathBuf>,
+}
+
+/// Get the directories that sound themes are installed in.
+fn base_dirs() -> Vec<PathBuf> {
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    let data_home = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| home.map(|home| home.join(".local/share")));
+    let data_dirs = std::env::var("XDG_DATA_DIRS")
+        .ok()
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| "/usr/local/share:/usr/share".into());
+    data_home
+        .into_iter()
+        .chain(data_dirs.split(':').map(PathBuf::from))
+        .map(|dir| dir.join("sounds"))
+        .collect()
+}
+
+/// Get the name of the sound theme that the desktop is configured to use.
+fn current_theme() -> String {
+    SettingsSchemaSource::default()
+        .and_then(|source| source.lookup("org.gnome.desktop.sound", true))
+        .map(|_| gio::Settings::new("org.gnome.desktop.sound").string("theme-name"))
+        .map(String::from)
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| FALLBACK_THEME.into())
+}
+
+/// Add a theme and the themes that it inherits from, unless it was already added.
+fn add_theme(base_dirs: &[PathBuf], name: &str, themes: &mut Vec<Theme>) {
+    if themes.iter().any(|theme| theme.name == name) {
+        return;
+    }
+    let mut dirs = vec![];
+    let mut inherits = vec![];
+    for base in base_dirs {
+        let root = base.join(name);
+        let Ok(index) = std::fs::read_to_string(root.join("index.theme")) else {
+            continue;
+        };
+        let (subdirs, parents) = parse_index(&index);
+        dirs.extend(subdirs.iter().map(|subdir| root.join(subdir)));
+        inherits.extend(parents);
+    }
+    themes.push(Theme {
+        name: name.into(),
+        dirs,
+    });
+    for parent in inherits {
+        add_theme(base_dirs, &parent, themes);
+    }
+}
+
+/// Get the sound directories and the inherited themes from an index.theme file.
+fn parse_index(index: &str) -> (Vec<String>, Vec<String>) {
+    let mut directories = vec![];
+    let mut inherits = vec![];
+    let mut in_theme_section = false;
+    for line in index.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_theme_section = line == "[Sound Theme]";
+            continue;
+        }
+        if !in_theme_section {
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let list = value
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty() && !Path::new(item).is_absolute())
+            .map(str::to_owned);
+        match key.trim() {
+            "Directories" => directories.extend(list),
+            "Inherits" => inherits.extend(list),
+            _ => {}
+        }
+    }
+    (directories, inherits)
+}
@@ -0,0 +1,107 @@
+//! Keeping a list of the nodes that can be monitored, for choosing one in the UI.
+
+use crate::pattern;
+use anyhow::Result;
+use pipewire::core::Core;
+use pipewire::keys;
+use pipewire::registry::{self, Registry};
+use pipewire::types::ObjectType;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+
+/// A node that can be monitored.
+#[derive(Debug, Clone)]
+pub struct SourceNode {
+    /// The global id of the node, which a stream can connect to.
+    pub id: u32,
+    /// The node name, which can be used as a `--target`.
+    pub name: String,
+    /// A human-readable description, or the name if there is none.
+    pub description: String,
+}
+
+/// The nodes that are currently present, shared with other threads.
+#[derive(Default)]
+pub struct SourceList(Mutex<BTreeMap<u32, SourceNode>>);
+
+impl SourceList {
+    /// Get all present nodes, sorted by their description.
+    pub fn nodes(&self) -> Vec<SourceNode> {
+        let mut nodes: Vec<SourceNode> = self.0.lock().unwrap().values().cloned().collect();
+        nodes.sort_by(|a, b| a.description.cmp(&b.description));
+        nodes
+    }
+
+    /// Check whether the node with the given id is present.
+    pub fn contains(&self, id: u32) -> bool {
+        self.0.lock().unwrap().contains_key(&id)
+    }
+}
+
+/// Keeps the registry listener alive.
+pub struct SourceWatcher {
+    _registry: Registry,
+    _listener: registry::Listener,
+}
+
+/// Keep `list` up to date with the nodes of the given media class, except for
+/// those whose name matches one of the `exclude` patterns, and call `on_change`
+/// whenever a node was added or removed.
+pub fn watch(
+    core: &Core,
+    media_class: &'static str,
+    exclude: Vec<String>,
+    list: Arc<SourceList>,
+    on_change: impl Fn() + 'static,
+) -> Result<SourceWatcher> {
+    let on_change = Rc::new(on_change);
+    let registry = core.get_registry()?;
+    let listener = registry
+        .add_listener_local()
+        .global({
+            let list = list.clone();
+            let on_change = on_change.clone();
+            move |global| {
+                if global.type_ != ObjectType::Node {
+                    return;
+                }
+                let Some(props) = global.props else {
+                    return;
+                };
+                if props.get(*keys::MEDIA_CLASS) != Some(media_class) {
+                    return;
+                }
+                let Some(name) = props.get(*keys::NODE_NAME) else {
+                    return;
+                };
+                if exclude
+                    .iter()
+                    .any(|pattern| pattern::matches(pattern, name))
+                {
+                    return;
+                }
+                let description = props.get(*keys::NODE_DESCRIPTION).unwrap_or(name);
+                list.0.lock().unwrap().insert(
+                    global.id,
+                    SourceNode {
+                        id: global.id,
+                        name: name.to_owned(),
+                        description: description.to_owned(),
+                    },
+                );
+                on_change();
+            }
+        })
+        .global_remove(move |id| {
+            if list.0.lock().unwrap().remove(&id).is_some() {
+                on_change();
+            }
+        })
+        .register();
+
+    Ok(SourceWatcher {
+        _registry: registry,
+        _listener: listener,
+    })
+}
@@ -0,0 +1,89 @@
+//! An FFT-based detector, which only considers the speech band and discounts
+//! tonal noises like fans or mains hum.
+
+use crate::detector::{Detector, Measurement};
+use rustfft::num_complex::Complex;
+use rustfft::{Fft, FftPlanner};
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+/// Lower and upper frequency in Hz of the band that is considered.
+const SPEECH_BAND: (f32, f32) = (300., 3400.);
+/// Number of bins on each side that make up the neighbourhood of a bin.
+const NEIGHBOURHOOD: usize = 8;
+/// Bins that exceed the median of their neighbourhood by more than this
+/// factor are considered tonal, and are limited to it.
+const TONAL_RATIO: f32 = 4.;
+
+pub struct Spectral {
+    fft: Arc<dyn Fft<f32>>,
+    n_channels: usize,
+    window: Vec<f32>,
+    window_power: f32,
+    band: (usize, usize),
+    buffer: Vec<Complex<f32>>,
+    scratch: Vec<Complex<f32>>,
+    power: Vec<f32>,
+}
+
+impl Spectral {
+    pub fn new(rate: u32, n_channels: u32, window_frames: usize) -> Self {
+        let size = window_frames.next_power_of_two();
+        let fft = FftPlanner::new().plan_fft_forward(size);
+
+        let window: Vec<f32> = (0..window_frames)
+            .map(|n| 0.5 - 0.5 * (2. * PI * n as f32 / window_frames as f32).cos())
+            .collect();
+        let window_power = window.iter().map(|w| w * w).sum();
+
+        let bin = |freq: f32| ((freq * size as f32 / rate as f32) as usize).min(size / 2);
+        Self {
+            n_channels: n_channels.max(1) as usize,
+            window,
+            window_power,
+            band: (bin(SPEECH_BAND.0), bin(SPEECH_BAND.1)),
+            buffer: vec![Complex::default(); size],
+            scratch: vec![Complex::default(); fft.get_inplace_scratch_len()],
+            power: vec![0.; size / 2 + 1],
+            fft,
+        }
+    }
+}
+
+impl Detector for Spectral {
+    fn measure(&mut self, samples: &mut [f32]) -> Measurement {
+        self.buffer.fill(Complex::default());
+        for ((frame, out), w) in samples
+            .chunks_exact(self.n_channels)
+            .zip(self.buffer.iter_mut())
+            .zip(self.window.iter())
+        {
+            let mono = frame.iter().sum::<f32>() / self.n_channels as f32;
+            out.re = mono * w;
+        }
+        self.fft
+            .process_with_scratch(&mut self.buffer, &mut self.scratch);
+        for (power, bin) in self.power.iter_mut().zip(self.buffer.iter()) {
+            *power = bin.norm_sqr();
+        }
+
+        let mut neighbourhood = [0f32; 2 * NEIGHBOURHOOD + 1];
+        let mut band_power = 0f32;
+        for k in self.band.0..self.band.1 {
+            let start = k.saturating_sub(NEIGHBOURHOOD);
+            let end = (k + NEIGHBOURHOOD + 1).min(self.power.len());
+            let neighbours = &mut neighbourhood[..end - start];
+            neighbours.copy_from_slice(&self.power[start..end]);
+            neighbours.sort_unstable_by(f32::total_cmp);
+            let median = neighbours[neighbours.len() / 2];
+            band_power += self.power[k].min(median * TONAL_RATIO);
+        }
+
+        // By Parseval's theorem, this is the RMS level of the signal within the band.
+        let rms = (2. * band_power / (self.buffer.len() as f32 * self.window_power)).sqrt();
+        Measurement {
+            level: rms,
+            speech: true,
+        }
+    }
+}
@@ -0,0 +1,28 @@
+//! Spoken announcements through speech-dispatcher.
+
+use std::process::{Command, Stdio};
+use std::thread;
+
+/// Have speech-dispatcher say a short message, through its `spd-say` client.
+///
+/// This only starts `spd-say`, which is waited for on a separate thread, so
+/// that connecting to (or starting) speech-dispatcher does not delay the sounds.
+pub fn say(text: &str) {
+    let child = Command::new("spd-say")
+        .args(["--application-name", "pw-micclick", "--priority", "message"])
+        .arg(text)
+        .stdin(Stdio::null())
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(e) => {
+            eprintln!("error: cannot run spd-say: {e}");
+            return;
+        }
+    };
+    thread::spawn(move || match child.wait() {
+        Ok(status) if status.success() => {}
+        Ok(status) => eprintln!("error: spd-say failed: {status}"),
+        Err(e) => eprintln!("error: cannot wait for spd-say: {e}"),
+    });
+}
@@ -0,0 +1,796 @@
+//! The state that the tray icon shows, shared by the tray implementations.
+
+use crate::controls::Controls;
+use crate::source_list::{SourceList, SourceNode};
+use crate::{
+    Args, Event, Loudness, Meter, MicEvent, Request, SourceId, CLIPPING_WARNING_TIME,
+    MUTED_FLASH_TIME, MUTED_ICON, THRESHOLD_NOTICE_TIME, THRESHOLD_STEP,
+};
+use std::collections::VecDeque;
+use std::future::{poll_fn, Future};
+use std::path::PathBuf;
+use std::pin::pin;
+use std::process::{Command, Stdio};
+use std::sync::{mpsc, Arc, Mutex};
+use std::task::Poll;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The implementations of the tray icon that this build supports.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum TrayBackend {
+    /// libappindicator, which needs GTK.
+    #[cfg(feature = "appindicator")]
+    #[cfg_attr(feature = "appindicator", default)]
+    Appindicator,
+    /// A StatusNotifierItem, talking D-Bus directly.
+    #[cfg(feature = "ksni")]
+    #[cfg_attr(not(feature = "appindicator"), default)]
+    Sni,
+}
+
+/// A corner of the screen.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, clap::ValueEnum)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+/// Run the tray icon with the given implementation until the event channel is closed.
+pub fn thread_main(
+    backend: TrayBackend,
+    eventreceiver: async_channel::Receiver<Event>,
+    state: TrayState,
+    actions: TrayActions,
+) {
+    match backend {
+        #[cfg(feature = "appindicator")]
+        TrayBackend::Appindicator => crate::indicator::thread_main(eventreceiver, state, actions),
+        #[cfg(feature = "ksni")]
+        TrayBackend::Sni => crate::sni::thread_main(eventreceiver, state, actions),
+    }
+}
+
+/// What the tray icon shows for one source.
+struct TraySource {
+    icon: Arc<str>,
+    /// Whether the source is on.
+    hot: bool,
+    /// Whether the capture stream is suspended.
+    suspended: bool,
+    muted: bool,
+    /// Whether the capture stream failed.
+    failed: bool,
+    /// The last measured input level.
+    level: Option<Meter>,
+    /// Since when the source is on.
+    hot_since: Option<Instant>,
+}
+
+impl TraySource {
+    fn new(suspended_icon: &Arc<str>) -> Self {
+        Self {
+            icon: suspended_icon.clone(),
+            hot: false,
+            suspended: true,
+            muted: false,
+            failed: false,
+            level: None,
+            hot_since: None,
+        }
+    }
+}
+
+/// How many periods the activity log keeps.
+const ACTIVITY_LOG_LEN: usize = 500;
+
+/// A period in which a source was on, for the activity log.
+pub struct Activity {
+    pub source: SourceId,
+    /// The local time at which the source turned on.
+    pub start: glib::DateTime,
+    /// How long the source was on, or None while it still is.
+    pub duration: Option<Duration>,
+}
+
+/// Which part of the tray has to be redrawn after an event.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Update {
+    Nothing,
+    Icon,
+    Cameras,
+    /// The input level of the shown source changed, which only shows in the
+    /// icon with --tray-level.
+    Level,
+    /// The sources that can be selected changed.
+    Sources,
+}
+
+/// Everything that the tray icons show, kept up to date from the events.
+pub struct TrayState {
+    labels: Vec<String>,
+    args: Args,
+    active_icon: Arc<str>,
+    inactive_icon: Arc<str>,
+    suspended_icon: Arc<str>,
+    sources: Vec<TraySource>,
+    /// The source whose state is shown by the icon.
+    shown: SourceId,
+    warning_until: Option<Instant>,
+    /// The adjusted on threshold in dB, and until when the label shows it.
+    threshold_notice: Option<(f32, Instant)>,
+    recorders: Vec<Arc<str>>,
+    /// Bluetooth headsets that use a profile with their microphone.
+    headsets: Vec<Arc<str>>,
+    /// Applications that use a camera.
+    cameras: Vec<Arc<str>>,
+    paused: bool,
+    /// Whether the user muted the clicks.
+    silenced: bool,
+    started: Instant,
+    /// The most recent periods in which a source was on, oldest first.
+    activity: VecDeque<Activity>,
+    /// Counts the changes to `activity`.
+    activity_changes: u64,
+}
+
+impl TrayState {
+    pub fn new(labels: Vec<String>, args: Args) -> Self {
+        let icon = |name: &Option<String>, default: &str| -> Arc<str> {
+            name.as_deref().unwrap_or(default).into()
+        };
+        let active_icon = icon(&args.icon_active, "microphone-sensitivity-high-symbolic");
+        let inactive_icon = icon(&args.icon_inactive, "microphone-sensitivity-low-symbolic");
+        let suspended_icon = icon(
+            &args.icon_suspended,
+            "microphone-sensitivity-muted-symbolic",
+        );
+        let sources = labels
+            .iter()
+            .map(|_| TraySource::new(&suspended_icon))
+            .collect();
+        Self {
+            labels,
+            args,
+            active_icon,
+            inactive_icon,
+            suspended_icon,
+            sources,
+            shown: 0,
+            warning_until: None,
+            threshold_notice: None,
+            recorders: Vec::new(),
+            headsets: Vec::new(),
+            cameras: Vec::new(),
+            paused: false,
+            silenced: false,
+            started: Instant::now(),
+            activity: VecDeque::new(),
+            activity_changes: 0,
+        }
+    }
+
+    pub fn args(&self) -> &Args {
+        &self.args
+    }
+
+    /// Whether a source can be selected in the tray, which is only the case
+    /// while a single one is monitored.
+    pub fn can_select_source(&self) -> bool {
+        self.labels.len() == 1
+    }
+
+    /// Get the label of a source, as shown in the tooltip.
+    pub fn source_label(&self, source: SourceId) -> &str {
+        &self.labels[source]
+    }
+
+    /// Get the most recent periods in which a source was on, oldest first.
+    pub fn activity(&self) -> &VecDeque<Activity> {
+        &self.activity
+    }
+
+    /// Get a number that changes whenever the activity log changes.
+    pub fn activity_changes(&self) -> u64 {
+        self.activity_changes
+    }
+
+    /// Apply an event, and tell what has to be redrawn because of it.
+    pub fn handle(&mut self, event: Event) -> Update {
+        let Some(source) = event.source else {
+            match event.event {
+                MicEvent::RecordingStarted(app) => self.recorders.push(app),
+                MicEvent::RecordingStopped(app) => remove(&mut self.recorders, &app),
+                MicEvent::HeadsetMicOn(headset) => self.headsets.push(headset),
+                MicEvent::HeadsetMicOff(headset) => remove(&mut self.headsets, &headset),
+                MicEvent::Paused(paused) => self.paused = paused,
+                MicEvent::Silenced(silenced) => self.silenced = silenced,
+                MicEvent::CameraStarted(app) => {
+                    self.cameras.push(app);
+                    return Update::Cameras;
+                }
+                MicEvent::CameraStopped(app) => {
+                    remove(&mut self.cameras, &app);
+                    return Update::Cameras;
+                }
+                MicEvent::SourcesChanged => return Update::Sources,
+                _ => return Update::Nothing,
+            }
+            return Update::Icon;
+        };
+        let state = &mut self.sources[source];
+        match event.event {
+            MicEvent::Active => {
+                state.icon = self.active_icon.clone();
+                state.hot = true;
+                state.suspended = false;
+                state.failed = false;
+            }
+            MicEvent::Releasing => {
+                state.icon = "microphone-sensitivity-medium-symbolic".into();
+                state.hot = true;
+                state.suspended = false;
+                state.failed = false;
+            }
+            MicEvent::Loudness(loudness) => {
+                state.icon = match loudness {
+                    Loudness::Quiet => "microphone-sensitivity-medium-symbolic".into(),
+                    Loudness::Normal => self.active_icon.clone(),
+                    Loudness::Loud => "audio-volume-overamplified-symbolic".into(),
+                };
+            }
+            MicEvent::Inactive => {
+                state.icon = self.inactive_icon.clone();
+                state.hot = false;
+                state.suspended = false;
+                state.failed = false;
+            }
+            MicEvent::Suspended => {
+                state.icon = self.suspended_icon.clone();
+                state.hot = false;
+                state.suspended = true;
+                state.failed = false;
+            }
+            MicEvent::NoDevice => {
+                state.icon = "microphone-disabled-symbolic".into();
+                state.hot = false;
+                state.suspended = false;
+                state.failed = false;
+            }
+            MicEvent::Error => {
+                state.icon = "dialog-error-symbolic".into();
+                state.hot = false;
+                state.suspended = false;
+                state.failed = true;
+            }
+            MicEvent::Muted => state.muted = true,
+            MicEvent::Unmuted => state.muted = false,
+            MicEvent::Clipping => {
+                self.warning_until = Some(Instant::now() + CLIPPING_WARNING_TIME);
+            }
+            MicEvent::Level(meter) => {
+                state.level = Some(meter);
+                return if source == self.shown {
+                    Update::Level
+                } else {
+                    Update::Nothing
+                };
+            }
+            MicEvent::RecordingStarted(_)
+            | MicEvent::RecordingStopped(_)
+            | MicEvent::HeadsetMicOn(_)
+            | MicEvent::HeadsetMicOff(_)
+            | MicEvent::CameraStarted(_)
+            | MicEvent::CameraStopped(_)
+            | MicEvent::Paused(_)
+            | MicEvent::Silenced(_)
+            | MicEvent::TestSound(_)
+            | MicEvent::SourcesChanged => return Update::Nothing,
+        }
+        if !state.hot {
+            if let Some(since) = state.hot_since.take() {
+                if let Some(activity) = self
+                    .activity
+                    .iter_mut()
+                    .rev()
+                    .find(|a| a.source == source && a.duration.is_none())
+                {
+                    activity.duration = Some(since.elapsed());
+                    self.activity_changes += 1;
+                }
+            }
+        } else if state.hot_since.is_none() {
+            state.hot_since = Some(Instant::now());
+            match glib::DateTime::now_local() {
+                Ok(start) => {
+                    if self.activity.len() == ACTIVITY_LOG_LEN {
+                        self.activity.pop_front();
+                    }
+                    self.activity.push_back(Activity {
+                        source,
+                        start,
+                        duration: None,
+                    });
+                    self.activity_changes += 1;
+                }
+                Err(e) => eprintln!("error: cannot get the local time: {e}"),
+            }
+        }
+        // Prefer showing a source that is on, otherwise the one that changed last.
+        self.shown = self.sources.iter().position(|s| s.hot).unwrap_or(source);
+        Update::Icon
+    }
+
+    /// Briefly show the given linear on threshold, after it was adjusted.
+    fn show_threshold(&mut self, threshold: f32) {
+        let until = Instant::now() + THRESHOLD_NOTICE_TIME;
+        self.threshold_notice = Some((self.args.db(threshold), until));
+    }
+
+    /// Wait for the next event or threshold notice and apply it, or wait until
+    /// the icon changes by itself, and tell what has to be redrawn. Returns
+    /// None once the event channel is closed.
+    ///
+    /// The notices come from the tray's own `adjust_threshold` calls, and keep
+    /// their channel out of the event channel so that it can still close.
+    ///
+    /// This needs a thread-default main context to wait on.
+    pub async fn next_update(
+        &mut self,
+        eventreceiver: &async_channel::Receiver<Event>,
+        notices: &async_channel::Receiver<f32>,
+    ) -> Option<Update> {
+        let wakeup = match self.next_tick() {
+            Some(at) => {
+                let timeout = at.saturating_duration_since(Instant::now());
+                glib::future_with_timeout(timeout, recv(eventreceiver, notices)).await
+            }
+            None => Ok(recv(eventreceiver, notices).await),
+        };
+        match wakeup {
+            Ok(Wakeup::Event(Ok(event))) => Some(self.handle(event)),
+            Ok(Wakeup::Event(Err(async_channel::RecvError))) => None,
+            Ok(Wakeup::Notice(threshold)) => {
+                self.show_threshold(threshold);
+                Some(Update::Icon)
+            }
+            Err(glib::FutureWithTimeoutError) if self.tick() => Some(Update::Icon),
+            Err(glib::FutureWithTimeoutError) => Some(Update::Nothing),
+        }
+    }
+
+    /// Check whether the icon changed by itself since the last call, because
+    /// it is flashing, a warning or notice ran out or the talk timer went on.
+    fn tick(&mut self) -> bool {
+        // Keep flashing the icon while there is input on a muted source, and
+        // keep counting the talk time.
+        let mut changed = self.flashing() || self.talk_time().is_some();
+        if self
+            .warning_until
+            .is_some_and(|until| Instant::now() >= until)
+        {
+            self.warning_until = None;
+            changed = true;
+        }
+        if self
+            .threshold_notice
+            .is_some_and(|(_, until)| Instant::now() >= until)
+        {
+            self.threshold_notice = None;
+            changed = true;
+        }
+        changed
+    }
+
+    /// Get the time at which the icon changes by itself next, if it does.
+    fn next_tick(&self) -> Option<Instant> {
+        let flash = self.flashing().then(|| {
+            let phases = self.started.elapsed().as_millis() / MUTED_FLASH_TIME.as_millis();
+            self.started + MUTED_FLASH_TIME * (phases as u32 + 1)
+        });
+        let timer = self
+            .talk_time()
+            .map(|(since, time)| since + Duration::from_secs(time.as_secs() + 1));
+        let notice = self.threshold_notice.map(|(_, until)| until);
+        [flash, self.warning_until, notice, timer]
+            .into_iter()
+            .flatten()
+            .min()
+    }
+
+    /// Get since when and for how long the shown source has been on, with --talk-timer.
+    fn talk_time(&self) -> Option<(Instant, Duration)> {
+        let since = self.sources[self.shown].hot_since?;
+        self.args.talk_timer.then(|| (since, since.elapsed()))
+    }
+
+    fn flashing(&self) -> bool {
+        self.sources.iter().any(|s| s.hot && s.muted)
+    }
+
+    /// Get the name of the icon to show, and a description of it.
+    pub fn icon(&self) -> (&str, &'static str) {
+        let state = &self.sources[self.shown];
+        if self.paused {
+            ("media-playback-pause-symbolic", "paused")
+        } else if self.warning_until.is_some() {
+            ("dialog-warning-symbolic", "clipping")
+        } else if state.muted && state.hot && flash_phase(self.started) {
+            ("dialog-warning-symbolic", "talking while muted")
+        } else if state.muted {
+            (MUTED_ICON, "icon")
+        } else if !state.hot && !self.headsets.is_empty() {
+            ("audio-headset-symbolic", "headset")
+        } else {
+            (&state.icon, "icon")
+        }
+    }
+
+    /// Whether the icon should demand attention, because there is input on
+    /// the shown source while it is muted.
+    pub fn needs_attention(&self) -> bool {
+        let state = &self.sources[self.shown];
+        !self.paused && state.muted && state.hot
+    }
+
+    /// Whether the shown source is on and monitoring is not paused.
+    pub fn active(&self) -> bool {
+        !self.paused && self.sources[self.shown].hot
+    }
+
+    /// Whether the icon should be shown at all.
+    pub fn visible(&self) -> bool {
+        // While paused, the icon stays visible so that monitoring can be resumed.
+        self.paused || !self.sources.iter().all(|s| s.suspended)
+    }
+
+    /// Get the last measured input level of the shown source.
+    pub fn meter(&self) -> Option<Meter> {
+        self.sources[self.shown].level
+    }
+
+    /// Get the label next to the icon, with the talk time and the input level
+    /// of the shown source, with --talk-timer and --tray-level, or briefly
+    /// the threshold after it was adjusted.
+    pub fn label(&self) -> Option<String> {
+        if let Some((threshold, _)) = self.threshold_notice {
+            return Some(format!("Threshold {threshold:.0} dB"));
+        }
+        let timer = self
+            .talk_time()
+            .map(|(_, time)| format!("{} live", format_time(time)));
+        let level = self
+            .meter()
+            .filter(|_| self.args.tray_level)
+            .map(|meter| format!("{:.1} dB", self.args.db(meter.level)));
+        match (timer, level) {
+            (Some(timer), Some(level)) => Some(format!("{timer}  {level}")),
+            (timer, level) => timer.or(level),
+        }
+    }
+
+    /// Get the title of the icon, which is also used as its tooltip.
+    pub fn title(&self) -> String {
+        let mut title = tray_title(&self.labels, &self.sources, &self.recorders, &self.headsets);
+        if let Some((_, time)) = self.talk_time() {
+            title = format!("{title}\n{} live", format_time(time));
+        }
+        match self.meter().filter(|_| self.args.tray_level) {
+            Some(meter) => format!(
+                "{title}\nInput level {:.1} dB (peak {:.1} dB)",
+                self.args.db(meter.level),
+                self.args.db(meter.peak)
+            ),
+            None => title,
+        }
+    }
+
+    /// Get the title of the camera icon, or None if it should be hidden.
+    pub fn camera_title(&self) -> Option<String> {
+        match self.cameras.len() {
+            0 => None,
+            1 => Some(format!("{} is using the camera", self.cameras[0])),
+            _ => Some(format!(
+                "{} are using the camera",
+                join_names(&self.cameras)
+            )),
+        }
+    }
+
+    pub fn pause_label(&self) -> &'static str {
+        if self.paused {
+            "Resume monitoring"
+        } else {
+            "Pause monitoring"
+        }
+    }
+
+    pub fn silence_label(&self) -> &'static str {
+        if self.silenced {
+            "Unmute clicks"
+        } else {
+            "Mute clicks"
+        }
+    }
+}
+
+/// What woke up the tray.
+enum Wakeup {
+    Event(Result<Event, async_channel::RecvError>),
+    /// The tray adjusted the thresholds, to the given linear on threshold.
+    Notice(f32),
+}
+
+/// Wait for the next event, or the next threshold notice.
+async fn recv(
+    eventreceiver: &async_channel::Receiver<Event>,
+    notices: &async_channel::Receiver<f32>,
+) -> Wakeup {
+    let mut event = pin!(eventreceiver.recv());
+    let mut notice = pin!(notices.recv());
+    let mut notices_closed = false;
+    poll_fn(|cx| {
+        if !notices_closed {
+            match notice.as_mut().poll(cx) {
+                Poll::Ready(Ok(threshold)) => return Poll::Ready(Wakeup::Notice(threshold)),
+                Poll::Ready(Err(async_channel::RecvError)) => notices_closed = true,
+                Poll::Pending => {}
+            }
+        }
+        event.as_mut().poll(cx).map(Wakeup::Event)
+    })
+    .await
+}
+
+/// Remove the first occurrence of a name from a list.
+fn remove(names: &mut Vec<Arc<str>>, name: &Arc<str>) {
+    if let Some(i) = names.iter().position(|n| n == name) {
+        names.remove(i);
+    }
+}
+
+/// What the items of the tray menu do.
+#[derive(Clone)]
+pub struct TrayActions {
+    request_sender: pipewire::channel::Sender<Request>,
+    controls: Arc<Controls>,
+    clicker_sender: mpsc::Sender<Event>,
+    source_list: Arc<SourceList>,
+    /// The node that was selected in the tray, if any.
+    selected_source: Arc<Mutex<Option<u32>>>,
+    config_path: PathBuf,
+}
+
+impl TrayActions {
+    pub fn new(
+        request_sender: pipewire::channel::Sender<Request>,
+        controls: Arc<Controls>,
+        clicker_sender: mpsc::Sender<Event>,
+        source_list: Arc<SourceList>,
+        config_path: PathBuf,
+    ) -> Self {
+        Self {
+            request_sender,
+            controls,
+            clicker_sender,
+            source_list,
+            selected_source: Arc::default(),
+            config_path,
+        }
+    }
+
+    pub fn toggle_pause(&self) {
+        let _ = self.request_sender.send(Request::TogglePause);
+    }
+
+    pub fn toggle_silenced(&self) {
+        let _ = self.request_sender.send(Request::ToggleSilenced);
+    }
+
+    /// Play the on (true) or off (false) sound.
+    pub fn test_sound(&self, on: bool) {
+        let _ = self.clicker_sender.send(Event {
+            source: None,
+            event: MicEvent::TestSound(on),
+        });
+    }
+
+    /// Raise or lower the thresholds by `step` dB, or reset them with None,
+    /// and have the tray show the new on threshold through `notices`.
+    pub fn adjust_threshold(&self, step: Option<f32>, notices: &async_channel::Sender<f32>) {
+        let adjustment = step.map_or(0., |step| self.controls.threshold_adjustment() + step);
+        self.controls.set_threshold_adjustment(adjustment);
+        if let Err(e) = notices.try_send(self.controls.thresholds().0) {
+            eprintln!("error: cannot show the adjusted threshold: {e}");
+        }
+    }
+
+    /// Get the linear on and off thresholds that currently apply.
+    pub fn thresholds(&self) -> (f32, f32) {
+        self.controls.thresholds()
+    }
+
+    /// Get the label of the menu item for adjusting the threshold.
+    pub fn threshold_label(&self) -> String {
+        let adjustment = self.controls.threshold_adjustment();
+        if adjustment == 0. {
+            "Threshold".to_owned()
+        } else {
+            format!("Threshold ({adjustment:+} dB)")
+        }
+    }
+
+    /// Use a new on threshold in dB, which the schedule and the adjustment apply
+    /// on top of. The off threshold keeps its distance below it.
+    pub fn set_threshold(&self, threshold: f32) {
+        let _ = self.request_sender.send(Request::SetThreshold(threshold));
+    }
+
+    pub fn set_hold_time(&self, hold_time: Duration) {
+        self.controls.set_hold_time(hold_time);
+    }
+
+    /// Get the sources that can be monitored.
+    pub fn sources(&self) -> Vec<SourceNode> {
+        self.source_list.nodes()
+    }
+
+    /// Get the node that was selected in the tray, or None if the source is
+    /// chosen automatically.
+    pub fn selected_source(&self) -> Option<u32> {
+        let mut selected = self.selected_source.lock().unwrap();
+        // The main thread goes back to the automatic choice once the node is removed.
+        if selected.is_some_and(|id| !self.source_list.contains(id)) {
+            *selected = None;
+        }
+        *selected
+    }
+
+    /// Monitor the given node instead of the first source, or go back to
+    /// choosing it automatically with None.
+    pub fn select_source(&self, id: Option<u32>) {
+        *self.selected_source.lock().unwrap() = id;
+        let _ = self.request_sender.send(Request::SelectSource(id));
+    }
+
+    /// Open the configuration file in the default editor, if there is one.
+    pub fn open_config(&self) {
+        let path = self.config_path.clone();
+        if !path.is_file() {
+            eprintln!("error: there is no config file at {}", path.display());
+            return;
+        }
+        // Do not block the tray while xdg-open hands the file over.
+        thread::spawn(move || {
+            let result = Command::new("xdg-open")
+                .arg(&path)
+                .stdin(Stdio::null())
+                .status();
+            match result {
+                Ok(status) if status.success() => {}
+                Ok(status) => eprintln!("error: xdg-open failed: {status}"),
+                Err(e) => eprintln!("error: cannot run xdg-open: {e}"),
+            }
+        });
+    }
+
+    /// Load the configuration file again, applying the thresholds, the hold
+    /// time and the schedule from it.
+    pub fn reload_config(&self) {
+        let _ = self.request_sender.send(Request::ReloadConfig);
+    }
+
+    pub fn quit(&self) {
+        let _ = self.request_sender.send(Request::Quit);
+    }
+}
+
+/// The label of the item in the source submenu that lets the source be chosen
+/// automatically again.
+pub const AUTOMATIC_SOURCE: &str = "Automatic";
+
+/// The items of the threshold submenu, with the step to adjust the thresholds by.
+pub fn threshold_steps() -> [(String, Option<f32>); 3] {
+    [
+        (
+            format!("Raise by {THRESHOLD_STEP} dB"),
+            Some(THRESHOLD_STEP),
+        ),
+        (
+            format!("Lower by {THRESHOLD_STEP} dB"),
+            Some(-THRESHOLD_STEP),
+        ),
+        ("Reset".to_owned(), None),
+    ]
+}
+
+/// Format a duration like "02:14", or "1:02:14" from an hour on.
+pub fn format_time(time: Duration) -> String {
+    let seconds = time.as_secs();
+    match seconds / 3600 {
+        0 => format!("{:02}:{:02}", seconds / 60, seconds % 60),
+        hours => format!("{hours}:{:02}:{:02}", seconds / 60 % 60, seconds % 60),
+    }
+}
+
+/// Whether a flashing icon is currently in its highlighted phase.
+fn flash_phase(since: Instant) -> bool {
+    since.elapsed().as_millis() / MUTED_FLASH_TIME.as_millis() % 2 == 0
+}
+
+/// Join names into a readable list, like "a, b and c".
+fn join_names<S: AsRef<str>>(names: &[S]) -> String {
+    match names {
+        [] => String::new(),
+        [name] => name.as_ref().to_owned(),
+        [names @ .., last] => {
+            let names: Vec<&str> = names.iter().map(AsRef::as_ref).collect();
+            format!("{} and {}", names.join(", "), last.as_ref())
+        }
+    }
+}
+
+/// Describe which sources are on, which applications are capturing audio and
+/// which Bluetooth headsets use their microphone, for the tray icon's title.
+fn tray_title(
+    labels: &[String],
+    sources: &[TraySource],
+    recorders: &[Arc<str>],
+    headsets: &[Arc<str>],
+) -> String {
+    let mut parts = Vec::new();
+    let failed: Vec<&String> = labels
+        .iter()
+        .zip(sources)
+        .filter(|(_, s)| s.failed)
+        .map(|(label, _)| label)
+        .collect();
+    if !failed.is_empty() {
+        parts.push(format!("Monitoring {} failed", join_names(&failed)));
+    }
+    // With a single source, the icon already tells whether it is on.
+    if labels.len() > 1 {
+        let hot: Vec<&String> = labels
+            .iter()
+            .zip(sources)
+            .filter(|(_, s)| s.hot)
+            .map(|(label, _)| label)
+            .collect();
+        match hot.len() {
+            0 => {}
+            1 => parts.push(format!("{} is on", hot[0])),
+            _ => parts.push(format!("{} are on", join_names(&hot))),
+        }
+    }
+    match recorders.len() {
+        0 => {}
+        1 => parts.push(format!("{} is recording", recorders[0])),
+        _ => parts.push(format!("{} are recording", join_names(recorders))),
+    }
+    match headsets.len() {
+        0 => {}
+        1 => parts.push(format!("{} is in headset mode", headsets[0])),
+        _ => parts.push(format!("{} are in headset mode", join_names(headsets))),
+    }
+    if parts.is_empty() {
+        "pw-micclick".to_owned()
+    } else {
+        parts.join("; ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_talk_time() {
+        assert_eq!(format_time(Duration::ZERO), "00:00");
+        assert_eq!(format_time(Duration::from_millis(134_900)), "02:14");
+        assert_eq!(format_time(Duration::from_secs(3599)), "59:59");
+        assert_eq!(format_time(Duration::from_secs(3600)), "1:00:00");
+        assert_eq!(format_time(Duration::from_secs(3734)), "1:02:14");
+    }
+}
@@ -0,0 +1,25 @@
+//! A simple voice activity detector based on signal energy and zero-crossing rate.
+
+use crate::noise_floor::NoiseFloor;
+use std::ops::RangeInclusive;
+
+/// Minimum ratio between a buffer's RMS level and the noise floor for it to be considered speech.
+const MIN_SNR: f32 = 3.;
+/// Zero crossings per second that are typical for (voiced and unvoiced) speech.
+const SPEECH_ZCR: RangeInclusive<f32> = 50.0..=5000.;
+
+#[derive(Debug, Default)]
+pub struct Vad {
+    noise_floor: NoiseFloor,
+}
+
+impl Vad {
+    /// Classify a buffer with the given RMS level and zero crossings per second.
+    ///
+    /// Returns true if the buffer likely contains speech. This also updates
+    /// the noise floor estimate, so it must be called for every buffer.
+    pub fn process(&mut self, rms: f32, zcr: f32) -> bool {
+        let noise_floor = self.noise_floor.update(rms);
+        rms > noise_floor * MIN_SNR && SPEECH_ZCR.contains(&zcr)
+    }
+}
@@ -0,0 +1,258 @@
+//! The first-run wizard, which measures the noise floor and the speech level
+//! like the calibrate subcommand, lets the user pick sounds, and writes the
+//! initial config file.
+
+use crate::calibrate::{self, Suggestion};
+use crate::config::{self, Settings};
+use crate::settings::{chosen_sound, save_and_apply, sound_chooser};
+use crate::tray::TrayActions;
+use crate::{Args, Meter};
+use gtk::prelude::*;
+use std::cell::RefCell;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// How long the wizard listens to silence, and then to speech.
+const MEASURE_TIME: Duration = Duration::from_secs(5);
+
+const INTRO: &str = "pw-micclick plays a sound when you start and stop talking. \
+    To tell your voice from the background noise, it first listens to a few \
+    seconds of silence, and then to a few seconds of you talking.";
+const SILENCE: &str = "Please stay silent…";
+const SPEECH: &str = "Now please talk normally, for example by reading this text aloud.";
+
+#[derive(Clone, Copy, PartialEq)]
+enum Phase {
+    Intro,
+    Silence,
+    Speech,
+    Done,
+}
+
+struct Measurement {
+    phase: Phase,
+    started: Instant,
+    /// The levels in dB during silence and speech.
+    silence: Vec<(Instant, f32)>,
+    speech: Vec<(Instant, f32)>,
+    suggestion: Option<Suggestion>,
+}
+
+/// The widgets that change between the steps of the wizard.
+#[derive(Clone)]
+struct Page {
+    text: gtk::Label,
+    progress: gtk::ProgressBar,
+    sounds: gtk::Grid,
+    next: gtk::Button,
+}
+
+/// The open wizard, which measures the levels that it is fed.
+pub struct Wizard {
+    measurement: Rc<RefCell<Measurement>>,
+    progress: gtk::ProgressBar,
+    args: Args,
+}
+
+impl Wizard {
+    /// Record the current input level of the shown source, while measuring.
+    pub fn level(&self, meter: Option<Meter>) {
+        let Some(meter) = meter else { return };
+        let mut measurement = self.measurement.borrow_mut();
+        let now = Instant::now();
+        // Like the calibrate subcommand, measure what the detector sees,
+        // before it is gated or smoothed.
+        let sample = (now, self.args.db(meter.ungated));
+        match measurement.phase {
+            Phase::Silence => measurement.silence.push(sample),
+            Phase::Speech => measurement.speech.push(sample),
+            Phase::Intro | Phase::Done => return,
+        }
+        let elapsed = now.duration_since(measurement.started);
+        self.progress
+            .set_fraction((elapsed.as_secs_f64() / MEASURE_TIME.as_secs_f64()).min(1.));
+    }
+}
+
+/// Show the wizard, which replaces `settings` with the suggested ones when the
+/// user saves them, or creates an empty config file when they skip it or close
+/// the window.
+pub fn show(
+    settings: Rc<RefCell<Settings>>,
+    config_path: Option<PathBuf>,
+    actions: TrayActions,
+    args: Args,
+) -> Wizard {
+    let current = settings.borrow().clone();
+    let window = gtk::Window::new(gtk::WindowType::Toplevel);
+    window.set_title("Welcome to pw-micclick");
+    window.set_border_width(12);
+    window.set_resizable(false);
+
+    let text = gtk::Label::new(Some(INTRO));
+    text.set_line_wrap(true);
+    text.set_max_width_chars(50);
+    text.set_xalign(0.);
+    let progress = gtk::ProgressBar::new();
+    let on_sound = sound_chooser("Choose the on sound", &current.on_sound);
+    let off_sound = sound_chooser("Choose the off sound", &current.off_sound);
+    let sounds = gtk::Grid::new();
+    sounds.set_row_spacing(6);
+    sounds.set_column_spacing(12);
+    for (row, (label, chooser)) in [("On sound", &on_sound), ("Off sound", &off_sound)]
+        .into_iter()
+        .enumerate()
+    {
+        let label = gtk::Label::new(Some(label));
+        label.set_halign(gtk::Align::Start);
+        sounds.attach(&label, 0, row as i32, 1, 1);
+        sounds.attach(chooser, 1, row as i32, 1, 1);
+    }
+    let note = gtk::Label::new(Some("Leave these empty for the built-in clicks."));
+    note.style_context().add_class("dim-label");
+    sounds.attach(&note, 0, 2, 2, 1);
+
+    let skip = gtk::Button::with_label("Skip");
+    skip.connect_clicked({
+        let window = window.clone();
+        move |_| window.close()
+    });
+    // However the wizard is closed, do not show it again on the next start.
+    window.connect_delete_event({
+        let config_path = config_path.clone();
+        move |_, _| {
+            if let Err(e) = config::create(config_path.as_deref()) {
+                eprintln!("error: cannot create the config file: {e:#}");
+            }
+            glib::Propagation::Proceed
+        }
+    });
+    let next = gtk::Button::with_label("Start");
+    let page = Page {
+        text,
+        progress: progress.clone(),
+        sounds: sounds.clone(),
+        next: next.clone(),
+    };
+    let measurement = Rc::new(RefCell::new(Measurement {
+        phase: Phase::Intro,
+        started: Instant::now(),
+        silence: vec![],
+        speech: vec![],
+        suggestion: None,
+    }));
+    next.connect_clicked({
+        let window = window.clone();
+        let page = page.clone();
+        let measurement = measurement.clone();
+        move |_| {
+            let suggestion = measurement.borrow().suggestion.clone();
+            let Some(suggestion) = suggestion else {
+                measure(&page, &measurement);
+                return;
+            };
+            let new = Settings {
+                threshold: suggestion.threshold,
+                hold_time: suggestion.hold_time,
+                on_sound: chosen_sound(&on_sound, &current.on_sound),
+                off_sound: chosen_sound(&off_sound, &current.off_sound),
+                target: current.target.clone(),
+            };
+            if save_and_apply(&window, &settings, config_path.as_deref(), &actions, new) {
+                window.close();
+            }
+        }
+    });
+    // Stop measuring once the window is closed.
+    window.connect_destroy({
+        let measurement = measurement.clone();
+        move |_| measurement.borrow_mut().phase = Phase::Done
+    });
+    let buttons = gtk::ButtonBox::new(gtk::Orientation::Horizontal);
+    buttons.set_layout(gtk::ButtonBoxStyle::End);
+    buttons.set_spacing(6);
+    buttons.add(&skip);
+    buttons.add(&next);
+
+    let content = gtk::Box::new(gtk::Orientation::Vertical, 12);
+    content.add(&page.text);
+    content.add(&progress);
+    content.add(&sounds);
+    content.add(&buttons);
+    window.add(&content);
+    window.show_all();
+    progress.hide();
+    sounds.hide();
+    Wizard {
+        measurement,
+        progress,
+        args,
+    }
+}
+
+/// Listen to silence and then to speech, and suggest settings for them.
+fn measure(page: &Page, measurement: &Rc<RefCell<Measurement>>) {
+    *measurement.borrow_mut() = Measurement {
+        phase: Phase::Silence,
+        started: Instant::now(),
+        silence: vec![],
+        speech: vec![],
+        suggestion: None,
+    };
+    page.text.set_text(SILENCE);
+    page.progress.set_fraction(0.);
+    page.progress.show();
+    page.sounds.hide();
+    page.next.set_sensitive(false);
+    let page = page.clone();
+    let measurement = measurement.clone();
+    glib::timeout_add_local_once(MEASURE_TIME, move || {
+        {
+            let mut measurement = measurement.borrow_mut();
+            if measurement.phase != Phase::Silence {
+                return;
+            }
+            measurement.phase = Phase::Speech;
+            measurement.started = Instant::now();
+        }
+        page.text.set_text(SPEECH);
+        page.progress.set_fraction(0.);
+        glib::timeout_add_local_once(MEASURE_TIME, move || suggest(&page, &measurement));
+    });
+}
+
+/// Show the settings that match the measured levels, or why there are none.
+fn suggest(page: &Page, measurement: &RefCell<Measurement>) {
+    let mut measurement = measurement.borrow_mut();
+    if measurement.phase != Phase::Speech {
+        return;
+    }
+    measurement.phase = Phase::Done;
+    page.progress.hide();
+    page.next.set_sensitive(true);
+    match calibrate::suggest(&measurement.silence, &measurement.speech) {
+        Ok(suggestion) => {
+            page.text.set_text(&format!(
+                "Your voice is at {:.0} dB, and the background noise at {:.0} dB. \
+                 pw-micclick will click when the level rises above {:.0} dB, and \
+                 again after {} ms below it. Pick your sounds, or change this later \
+                 in the settings.",
+                suggestion.speech_median,
+                suggestion.noise_floor,
+                suggestion.threshold,
+                suggestion.hold_time.as_millis()
+            ));
+            page.sounds.show_all();
+            page.next.set_label("Save");
+            measurement.suggestion = Some(suggestion);
+        }
+        Err(e) => {
+            page.text.set_text(&format!(
+                "Cannot suggest settings: {e:#}. Please check that the right \
+                 microphone is used, and try again."
+            ));
+            page.next.set_label("Try again");
+        }
+    }
+}